@@ -1,4 +1,9 @@
-use crate::expr::{self, Expr};
+use std::fmt::Display;
+
+use crate::{
+    expr::{self, Expr},
+    stmt::{self, Stmt},
+};
 
 pub struct AstPrinter {}
 
@@ -20,6 +25,133 @@ impl AstPrinter {
         string += ")";
         return string;
     }
+
+    // like parenthesize, for nodes whose children include statements
+    fn parenthesize_parts(&mut self, name: &str, parts: Vec<String>) -> String {
+        if parts.is_empty() {
+            return format!("({})", name);
+        }
+        return format!("({} {})", name, parts.join(" "));
+    }
+
+    fn pattern(&mut self, pattern: &expr::Pattern) -> String {
+        let mut names: Vec<String> = pattern
+            .names
+            .iter()
+            .map(|name| name.lexeme.clone())
+            .collect();
+        if let Some(rest) = &pattern.rest {
+            names.push(format!("...{}", rest.lexeme));
+        }
+        return format!("[{}]", names.join(" "));
+    }
+
+    fn stmts(&mut self, statements: &[Stmt]) -> Vec<String> {
+        return statements
+            .iter()
+            .map(|statement| statement.accept(self))
+            .collect();
+    }
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", AstPrinter::new().print(self))
+    }
+}
+
+impl Display for Stmt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.accept(&mut AstPrinter::new()))
+    }
+}
+
+impl stmt::Visitor for AstPrinter {
+    type Output = String;
+
+    fn visit_assert(&mut self, assert: &stmt::Assert) -> Self::Output {
+        let mut exprs = vec![assert.condition.as_ref()];
+        exprs.extend(assert.message.as_deref());
+        return self.parenthesize(&"assert".to_owned(), &exprs);
+    }
+
+    fn visit_block(&mut self, block: &stmt::Block) -> Self::Output {
+        let parts = self.stmts(&block.statements);
+        return self.parenthesize_parts("block", parts);
+    }
+
+    fn visit_destructure(&mut self, destructure: &stmt::Destructure) -> Self::Output {
+        let parts = vec![
+            self.pattern(&destructure.pattern),
+            destructure.initializer.accept(self),
+        ];
+        return self.parenthesize_parts("var", parts);
+    }
+
+    fn visit_expression(&mut self, expression: &stmt::Expression) -> Self::Output {
+        return self.parenthesize(&"expr".to_owned(), &vec![&expression.expression]);
+    }
+
+    fn visit_for_each(&mut self, for_each: &stmt::ForEach) -> Self::Output {
+        let parts = vec![
+            for_each.name.lexeme.clone(),
+            for_each.iterable.accept(self),
+            for_each.body.accept(self),
+        ];
+        return self.parenthesize_parts("for-in", parts);
+    }
+
+    fn visit_function(&mut self, function: &stmt::Function) -> Self::Output {
+        let params: Vec<String> = function
+            .params
+            .iter()
+            .map(|param| param.lexeme.clone())
+            .collect();
+        let mut parts = vec![
+            function.name.lexeme.clone(),
+            format!("({})", params.join(" ")),
+        ];
+        parts.extend(self.stmts(&function.body));
+        return self.parenthesize_parts("fun", parts);
+    }
+
+    fn visit_if(&mut self, r#if: &stmt::If) -> Self::Output {
+        let mut parts = vec![r#if.condition.accept(self), r#if.then_branch.accept(self)];
+        if let Some(else_branch) = &r#if.else_branch {
+            parts.push(else_branch.accept(self));
+        }
+        return self.parenthesize_parts("if", parts);
+    }
+
+    fn visit_print(&mut self, print: &stmt::Print) -> Self::Output {
+        return self.parenthesize(&"print".to_owned(), &vec![&print.expression]);
+    }
+
+    fn visit_repeat(&mut self, repeat: &stmt::Repeat) -> Self::Output {
+        let parts = vec![repeat.count.accept(self), repeat.body.accept(self)];
+        return self.parenthesize_parts("repeat", parts);
+    }
+
+    fn visit_return(&mut self, r#return: &stmt::Return) -> Self::Output {
+        return self.parenthesize(&"return".to_owned(), &r#return.value.iter().collect());
+    }
+
+    fn visit_var(&mut self, var: &stmt::Var) -> Self::Output {
+        let keyword = if var.constant { "const" } else { "var" };
+        let mut parts = vec![var.name.lexeme.clone()];
+        if let Some(initializer) = &var.initializer {
+            parts.push(initializer.accept(self));
+        }
+        return self.parenthesize_parts(keyword, parts);
+    }
+
+    fn visit_while(&mut self, r#while: &stmt::While) -> Self::Output {
+        let mut parts = vec![r#while.condition.accept(self), r#while.body.accept(self)];
+        if let Some(increment) = &r#while.increment {
+            parts.push(increment.accept(self));
+        }
+        return self.parenthesize_parts("while", parts);
+    }
 }
 
 impl expr::Visitor for AstPrinter {
@@ -33,14 +165,35 @@ impl expr::Visitor for AstPrinter {
         return self.parenthesize(&binary.operator.lexeme, &vec![&binary.left, &binary.right]);
     }
 
+    fn visit_block(&mut self, block: &expr::Block) -> Self::Output {
+        let mut parts = self.stmts(&block.statements);
+        if let Some(value) = &block.value {
+            parts.push(value.accept(self));
+        }
+        return self.parenthesize_parts("block", parts);
+    }
+
     fn visit_call(&mut self, call: &expr::Call) -> Self::Output {
         return self.parenthesize(&"function".to_owned(), &call.arguments.iter().collect());
     }
 
+    fn visit_destructure(&mut self, destructure: &expr::Destructure) -> String {
+        let pattern = self.pattern(&destructure.pattern);
+        return self.parenthesize(&pattern, &vec![&destructure.value]);
+    }
+
     fn visit_grouping(&mut self, grouping: &expr::Grouping) -> String {
         return self.parenthesize(&"group".to_string(), &vec![&grouping.expression]);
     }
 
+    fn visit_index(&mut self, index: &expr::Index) -> Self::Output {
+        return self.parenthesize(&"index".to_owned(), &vec![&index.object, &index.index]);
+    }
+
+    fn visit_list(&mut self, list: &expr::List) -> Self::Output {
+        return self.parenthesize(&"array".to_owned(), &list.elements.iter().collect());
+    }
+
     fn visit_literal(&mut self, literal: &expr::Literal) -> String {
         if literal.value.is_none() {
             return "nil".to_string();
@@ -55,6 +208,26 @@ impl expr::Visitor for AstPrinter {
         );
     }
 
+    fn visit_postfix(&mut self, postfix: &expr::Postfix) -> Self::Output {
+        return format!(
+            "(postfix{} {})",
+            postfix.operator.lexeme, postfix.name.lexeme
+        );
+    }
+
+    fn visit_slice(&mut self, slice: &expr::Slice) -> Self::Output {
+        let object = slice.object.accept(self);
+        let start = slice
+            .start
+            .as_ref()
+            .map_or("nil".to_string(), |start| start.accept(self));
+        let end = slice
+            .end
+            .as_ref()
+            .map_or("nil".to_string(), |end| end.accept(self));
+        return format!("(slice {} {} {})", object, start, end);
+    }
+
     fn visit_unary(&mut self, unary: &expr::Unary) -> String {
         return self.parenthesize(&unary.operator.lexeme, &vec![&unary.right]);
     }
@@ -63,3 +236,53 @@ impl expr::Visitor for AstPrinter {
         return variable.name.lexeme.clone();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{parser::parse_program, stmt::Stmt};
+
+    fn print(source: &str) -> Vec<String> {
+        return parse_program(source)
+            .expect("test source should parse")
+            .iter()
+            .map(|statement| statement.to_string())
+            .collect();
+    }
+
+    #[test]
+    fn list_literals_print_as_arrays() {
+        assert_eq!(
+            print("print [1, \"a\", [true]]; print [];"),
+            ["(print (array 1.0 a (array true)))", "(print (array))"]
+        );
+    }
+
+    #[test]
+    fn indexes_and_slices_print_their_parts() {
+        assert_eq!(
+            print("var x = [1, 2][0]; print x[1:];"),
+            [
+                "(var x (index (array 1.0 2.0) 0.0))",
+                "(print (slice x 1.0 nil))"
+            ]
+        );
+    }
+
+    #[test]
+    fn statements_display_as_s_expressions() {
+        assert_eq!(
+            print("fun f(a) { return -a; } while (x < 1) print x;"),
+            ["(fun f (a) (return (- a)))", "(while (< x 1.0) (print x))"]
+        );
+    }
+
+    #[test]
+    fn expressions_display_as_s_expressions() {
+        let statements = parse_program("1 + 2 * 3;").unwrap();
+        let expression = match &statements[0] {
+            Stmt::Expression(statement) => &statement.expression,
+            statement => panic!("expected an expression statement, got {}", statement),
+        };
+        assert_eq!(format!("{}", expression), "(+ 1.0 (* 2.0 3.0))");
+    }
+}
@@ -1,7 +1,11 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
 
 use crate::{
-    interpreter::{RuntimeError, RuntimeExceptions},
+    interpreter::{RuntimeError, RuntimeErrorKind, RuntimeExceptions},
     token::{LiteralValue, Token},
 };
 
@@ -9,6 +13,8 @@ use crate::{
 pub struct Environment {
     pub enclosing: Option<Rc<Environment>>,
     pub values: RefCell<HashMap<String, Option<LiteralValue>>>,
+    // names in this scope declared with 'const'
+    pub constants: RefCell<HashSet<String>>,
 }
 
 impl Environment {
@@ -16,6 +22,7 @@ impl Environment {
         Environment {
             enclosing: enclosing.map(|e| Rc::clone(e)),
             values: RefCell::new(HashMap::new()),
+            constants: RefCell::new(HashSet::new()),
         }
     }
 
@@ -31,7 +38,7 @@ impl Environment {
 
         return Err(RuntimeExceptions::RuntimeError(RuntimeError::new(
             name,
-            format!("Undefined variable '{}'.", name.lexeme).as_str(),
+            RuntimeErrorKind::UndefinedVariable(name.lexeme.clone()),
         )));
     }
 
@@ -42,6 +49,12 @@ impl Environment {
     ) -> Result<(), RuntimeExceptions> {
         let mut value_ref = self.values.borrow_mut();
         if value_ref.contains_key(&name.lexeme) {
+            if self.constants.borrow().contains(&name.lexeme) {
+                return Err(RuntimeExceptions::RuntimeError(RuntimeError::new(
+                    name,
+                    RuntimeErrorKind::ConstantAssignment(name.lexeme.clone()),
+                )));
+            }
             value_ref.insert(name.lexeme.clone(), value);
             return Ok(());
         }
@@ -52,11 +65,61 @@ impl Environment {
 
         return Err(RuntimeExceptions::RuntimeError(RuntimeError::new(
             name,
-            format!("Undefined variable '{}'.", name.lexeme).as_str(),
+            RuntimeErrorKind::UndefinedVariable(name.lexeme.clone()),
         )));
     }
 
     pub fn define(&self, name: String, value: Option<LiteralValue>) {
+        // redeclaring with 'var' lifts any earlier 'const'
+        self.constants.borrow_mut().remove(&name);
+        self.values.borrow_mut().insert(name, value);
+    }
+
+    pub fn define_constant(&self, name: String, value: Option<LiteralValue>) {
+        self.constants.borrow_mut().insert(name.clone());
         self.values.borrow_mut().insert(name, value);
     }
 }
+
+// the bindings of every scope in a chain at one moment. only bindings are restored: each
+// scope's names, values and constants are copied, but a list value is shared, so a list
+// mutated after the snapshot stays mutated. scopes are restored in place, so closures over
+// them see it too
+#[cfg(test)]
+pub struct EnvSnapshot {
+    environment: Rc<Environment>,
+    scopes: Vec<(
+        Rc<Environment>,
+        HashMap<String, Option<LiteralValue>>,
+        HashSet<String>,
+    )>,
+}
+
+#[cfg(test)]
+impl EnvSnapshot {
+    pub fn capture(environment: &Rc<Environment>) -> EnvSnapshot {
+        let mut scopes = Vec::new();
+        let mut current = Some(environment);
+        while let Some(scope) = current {
+            scopes.push((
+                Rc::clone(scope),
+                scope.values.borrow().clone(),
+                scope.constants.borrow().clone(),
+            ));
+            current = scope.enclosing.as_ref();
+        }
+        return EnvSnapshot {
+            environment: Rc::clone(environment),
+            scopes,
+        };
+    }
+
+    // puts every scope back the way it was and returns the innermost one
+    pub fn restore(&self) -> Rc<Environment> {
+        for (scope, values, constants) in &self.scopes {
+            *scope.values.borrow_mut() = values.clone();
+            *scope.constants.borrow_mut() = constants.clone();
+        }
+        return Rc::clone(&self.environment);
+    }
+}
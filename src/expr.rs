@@ -1,14 +1,27 @@
-use crate::token::{LiteralValue, Token};
+use crate::{
+    stmt::Stmt,
+    token::{LiteralValue, Token},
+};
+
+// identifies one expression or statement node, for tools that cache results per node. the
+// parser numbers nodes in the order it finishes them, so the same source always gets the same ids
+pub type NodeId = usize;
 
 pub trait Visitor {
     type Output;
 
     fn visit_assign(&mut self, assign: &Assign) -> Self::Output;
     fn visit_binary(&mut self, binary: &Binary) -> Self::Output;
+    fn visit_block(&mut self, block: &Block) -> Self::Output;
     fn visit_call(&mut self, call: &Call) -> Self::Output;
+    fn visit_destructure(&mut self, destructure: &Destructure) -> Self::Output;
     fn visit_grouping(&mut self, grouping: &Grouping) -> Self::Output;
+    fn visit_index(&mut self, index: &Index) -> Self::Output;
+    fn visit_list(&mut self, list: &List) -> Self::Output;
     fn visit_literal(&mut self, literal: &Literal) -> Self::Output;
     fn visit_logical(&mut self, logical: &Logical) -> Self::Output;
+    fn visit_postfix(&mut self, postfix: &Postfix) -> Self::Output;
+    fn visit_slice(&mut self, slice: &Slice) -> Self::Output;
     fn visit_unary(&mut self, unary: &Unary) -> Self::Output;
     fn visit_variable(&mut self, variable: &Variable) -> Self::Output;
 }
@@ -17,10 +30,16 @@ pub trait Visitor {
 pub enum Expr {
     Assign(Assign),
     Binary(Binary),
+    Block(Block),
     Call(Call),
+    Destructure(Destructure),
     Grouping(Grouping),
+    Index(Index),
+    List(List),
     Literal(Literal),
     Logical(Logical),
+    Postfix(Postfix),
+    Slice(Slice),
     Unary(Unary),
     Variable(Variable),
 }
@@ -30,25 +49,75 @@ impl Expr {
         return match self {
             Expr::Assign(assign) => visitor.visit_assign(assign),
             Expr::Binary(binary) => visitor.visit_binary(binary),
+            Expr::Block(block) => visitor.visit_block(block),
             Expr::Call(call) => visitor.visit_call(call),
+            Expr::Destructure(destructure) => visitor.visit_destructure(destructure),
             Expr::Grouping(grouping) => visitor.visit_grouping(grouping),
+            Expr::Index(index) => visitor.visit_index(index),
+            Expr::List(list) => visitor.visit_list(list),
             Expr::Literal(literal) => visitor.visit_literal(literal),
             Expr::Logical(logical) => visitor.visit_logical(logical),
+            Expr::Postfix(postfix) => visitor.visit_postfix(postfix),
+            Expr::Slice(slice) => visitor.visit_slice(slice),
             Expr::Unary(unary) => visitor.visit_unary(unary),
             Expr::Variable(variable) => visitor.visit_variable(variable),
         };
     }
+
+    // byte offsets (start, end) of the source this expression was parsed from
+    pub fn span(&self) -> (usize, usize) {
+        return match self {
+            Expr::Assign(assign) => assign.span,
+            Expr::Binary(binary) => binary.span,
+            Expr::Block(block) => block.span,
+            Expr::Call(call) => call.span,
+            Expr::Destructure(destructure) => destructure.span,
+            Expr::Grouping(grouping) => grouping.span,
+            Expr::Index(index) => index.span,
+            Expr::List(list) => list.span,
+            Expr::Literal(literal) => literal.span,
+            Expr::Logical(logical) => logical.span,
+            Expr::Postfix(postfix) => postfix.span,
+            Expr::Slice(slice) => slice.span,
+            Expr::Unary(unary) => unary.span,
+            Expr::Variable(variable) => variable.span,
+        };
+    }
+
+    #[cfg(test)]
+    pub fn id(&self) -> NodeId {
+        return match self {
+            Expr::Assign(assign) => assign.id,
+            Expr::Binary(binary) => binary.id,
+            Expr::Block(block) => block.id,
+            Expr::Call(call) => call.id,
+            Expr::Destructure(destructure) => destructure.id,
+            Expr::Grouping(grouping) => grouping.id,
+            Expr::Index(index) => index.id,
+            Expr::List(list) => list.id,
+            Expr::Literal(literal) => literal.id,
+            Expr::Logical(logical) => logical.id,
+            Expr::Postfix(postfix) => postfix.id,
+            Expr::Slice(slice) => slice.id,
+            Expr::Unary(unary) => unary.id,
+            Expr::Variable(variable) => variable.id,
+        };
+    }
 }
 
 #[derive(Clone, PartialEq, Debug)]
 pub struct Assign {
     pub name: Token,
     pub value: Box<Expr>,
+    pub span: (usize, usize),
+    pub id: NodeId,
 }
 
 impl Assign {
-    pub fn new(name: Token, value: Expr) -> Assign {
+    pub fn new(name: Token, value: Expr, id: NodeId) -> Assign {
         Assign {
+            id,
+            span: (name.offset, value.span().1),
             name,
             value: Box::new(value),
         }
@@ -60,11 +129,15 @@ pub struct Binary {
     pub left: Box<Expr>,
     pub operator: Token,
     pub right: Box<Expr>,
+    pub span: (usize, usize),
+    pub id: NodeId,
 }
 
 impl Binary {
-    pub fn new(left: Expr, operator: Token, right: Expr) -> Binary {
+    pub fn new(left: Expr, operator: Token, right: Expr, id: NodeId) -> Binary {
         Binary {
+            id,
+            span: (left.span().0, right.span().1),
             left: Box::new(left),
             operator,
             right: Box::new(right),
@@ -72,16 +145,45 @@ impl Binary {
     }
 }
 
+// a block whose value is its trailing expression, or nil if it doesn't end in one
+#[derive(Clone, PartialEq, Debug)]
+pub struct Block {
+    pub statements: Vec<Stmt>,
+    pub value: Option<Box<Expr>>,
+    pub span: (usize, usize),
+    pub id: NodeId,
+}
+
+impl Block {
+    pub fn new(
+        statements: Vec<Stmt>,
+        value: Option<Expr>,
+        span: (usize, usize),
+        id: NodeId,
+    ) -> Block {
+        Block {
+            id,
+            statements,
+            value: value.map(Box::new),
+            span,
+        }
+    }
+}
+
 #[derive(Clone, PartialEq, Debug)]
 pub struct Call {
     pub callee: Box<Expr>,
     pub paren: Token,
     pub arguments: Vec<Expr>,
+    pub span: (usize, usize),
+    pub id: NodeId,
 }
 
 impl Call {
-    pub fn new(callee: Expr, paren: Token, arguments: Vec<Expr>) -> Call {
+    pub fn new(callee: Expr, paren: Token, arguments: Vec<Expr>, id: NodeId) -> Call {
         Call {
+            id,
+            span: (callee.span().0, paren.end()),
             callee: Box::new(callee),
             paren,
             arguments,
@@ -89,15 +191,98 @@ impl Call {
     }
 }
 
+// `[a, b] = value`, assigning each name an element of a list
+#[derive(Clone, PartialEq, Debug)]
+pub struct Destructure {
+    pub pattern: Pattern,
+    pub value: Box<Expr>,
+    pub span: (usize, usize),
+    pub id: NodeId,
+}
+
+impl Destructure {
+    pub fn new(pattern: Pattern, value: Expr, id: NodeId) -> Destructure {
+        Destructure {
+            id,
+            span: (pattern.bracket.offset, value.span().1),
+            pattern,
+            value: Box::new(value),
+        }
+    }
+}
+
+// the `[a, b, ...rest]` of a destructuring declaration or assignment. not a node of its own
+#[derive(Clone, PartialEq, Debug)]
+pub struct Pattern {
+    pub bracket: Token,
+    pub names: Vec<Token>,
+    // takes a list of whatever elements are left after the names
+    pub rest: Option<Token>,
+}
+
+impl Pattern {
+    pub fn new(bracket: Token, names: Vec<Token>, rest: Option<Token>) -> Pattern {
+        Pattern {
+            bracket,
+            names,
+            rest,
+        }
+    }
+}
+
 #[derive(Clone, PartialEq, Debug)]
 pub struct Grouping {
     pub expression: Box<Expr>,
+    pub span: (usize, usize),
+    pub id: NodeId,
 }
 
 impl Grouping {
-    pub fn new(expression: Expr) -> Grouping {
+    pub fn new(expression: Expr, span: (usize, usize), id: NodeId) -> Grouping {
         Grouping {
+            id,
             expression: Box::new(expression),
+            span,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Index {
+    pub object: Box<Expr>,
+    pub bracket: Token,
+    pub index: Box<Expr>,
+    pub span: (usize, usize),
+    pub id: NodeId,
+}
+
+impl Index {
+    pub fn new(object: Expr, bracket: Token, index: Expr, id: NodeId) -> Index {
+        Index {
+            id,
+            span: (object.span().0, bracket.end()),
+            object: Box::new(object),
+            bracket,
+            index: Box::new(index),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct List {
+    pub bracket: Token,
+    pub elements: Vec<Expr>,
+    pub span: (usize, usize),
+    pub id: NodeId,
+}
+
+impl List {
+    pub fn new(bracket: Token, elements: Vec<Expr>, span: (usize, usize), id: NodeId) -> List {
+        List {
+            id,
+            bracket,
+            elements,
+            span,
         }
     }
 }
@@ -105,11 +290,13 @@ impl Grouping {
 #[derive(Clone, PartialEq, Debug)]
 pub struct Literal {
     pub value: Option<LiteralValue>,
+    pub span: (usize, usize),
+    pub id: NodeId,
 }
 
 impl Literal {
-    pub fn new(value: Option<LiteralValue>) -> Literal {
-        Literal { value }
+    pub fn new(value: Option<LiteralValue>, span: (usize, usize), id: NodeId) -> Literal {
+        Literal { id, value, span }
     }
 }
 
@@ -118,11 +305,15 @@ pub struct Logical {
     pub left: Box<Expr>,
     pub operator: Token,
     pub right: Box<Expr>,
+    pub span: (usize, usize),
+    pub id: NodeId,
 }
 
 impl Logical {
-    pub fn new(left: Expr, operator: Token, right: Expr) -> Logical {
+    pub fn new(left: Expr, operator: Token, right: Expr, id: NodeId) -> Logical {
         Logical {
+            id,
+            span: (left.span().0, right.span().1),
             left: Box::new(left),
             operator,
             right: Box::new(right),
@@ -130,15 +321,68 @@ impl Logical {
     }
 }
 
+// `name++` or `name--`: assigns the stepped value but evaluates to the old one
+#[derive(Clone, PartialEq, Debug)]
+pub struct Postfix {
+    pub name: Token,
+    pub operator: Token,
+    pub span: (usize, usize),
+    pub id: NodeId,
+}
+
+impl Postfix {
+    pub fn new(name: Token, operator: Token, id: NodeId) -> Postfix {
+        Postfix {
+            id,
+            span: (name.offset, operator.end()),
+            name,
+            operator,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Slice {
+    pub object: Box<Expr>,
+    pub bracket: Token,
+    pub start: Option<Box<Expr>>,
+    pub end: Option<Box<Expr>>,
+    pub span: (usize, usize),
+    pub id: NodeId,
+}
+
+impl Slice {
+    pub fn new(
+        object: Expr,
+        bracket: Token,
+        start: Option<Expr>,
+        end: Option<Expr>,
+        id: NodeId,
+    ) -> Slice {
+        Slice {
+            id,
+            span: (object.span().0, bracket.end()),
+            object: Box::new(object),
+            bracket,
+            start: start.map(|s| Box::new(s)),
+            end: end.map(|e| Box::new(e)),
+        }
+    }
+}
+
 #[derive(Clone, PartialEq, Debug)]
 pub struct Unary {
     pub operator: Token,
     pub right: Box<Expr>,
+    pub span: (usize, usize),
+    pub id: NodeId,
 }
 
 impl Unary {
-    pub fn new(operator: Token, right: Expr) -> Unary {
+    pub fn new(operator: Token, right: Expr, id: NodeId) -> Unary {
         Unary {
+            id,
+            span: (operator.offset, right.span().1),
             operator,
             right: Box::new(right),
         }
@@ -148,10 +392,16 @@ impl Unary {
 #[derive(Clone, PartialEq, Debug)]
 pub struct Variable {
     pub name: Token,
+    pub span: (usize, usize),
+    pub id: NodeId,
 }
 
 impl Variable {
-    pub fn new(name: Token) -> Variable {
-        Variable { name }
+    pub fn new(name: Token, id: NodeId) -> Variable {
+        Variable {
+            id,
+            span: (name.offset, name.end()),
+            name,
+        }
     }
 }
@@ -1,82 +1,217 @@
 use std::{
+    cell::RefCell,
+    fmt::Display,
+    io::{self, Write},
+    ops::RangeInclusive,
     rc::Rc,
-    time::{SystemTime, UNIX_EPOCH},
+    time::{Instant, SystemTime, UNIX_EPOCH},
 };
 
 use crate::{
     environment::Environment,
-    expr::{self, Expr},
-    lox_callables::{LoxAnonymous, LoxCallable, LoxCallables, LoxFunction},
-    runtime_error,
+    expr::{self, Expr, Pattern},
+    lox_callables::{LoxCallable, LoxCallables, LoxFunction},
+    natives::{self, Capabilities},
+    runtime_error, source_location,
     stmt::{self, Stmt},
     token::{LiteralValue, Token},
     token_type::TokenType,
+    warning,
 };
 
 pub enum RuntimeExceptions {
     RuntimeError(RuntimeError),
-    Return(Return),
+    // boxed, since they're much larger than the errors every Result here mostly carries
+    Return(Box<Return>),
+    TailCall(Box<TailCall>),
 }
 
 #[derive(Debug)]
 pub struct RuntimeError {
-    pub message: String,
+    pub kind: RuntimeErrorKind,
     pub token: Token,
 }
 
 impl RuntimeError {
-    pub fn new(token: &Token, message: &str) -> RuntimeError {
+    pub fn new(token: &Token, kind: RuntimeErrorKind) -> RuntimeError {
         RuntimeError {
             token: token.clone(),
-            message: message.to_string(),
+            kind,
+        }
+    }
+}
+
+// what went wrong, so embedders can match on it. Display gives the message shown to users
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeErrorKind {
+    UndefinedVariable(String),
+    ConstantAssignment(String),
+    ArityMismatch {
+        expected: RangeInclusive<usize>,
+        got: usize,
+    },
+    NotCallable,
+    // an operand or argument of the wrong type, with the message describing what was expected
+    TypeMismatch(&'static str),
+    InvalidIndex {
+        container: &'static str,
+    },
+    IndexOutOfRange {
+        container: &'static str,
+    },
+    // an arithmetic operator, by lexeme, given nil as an operand
+    NilArithmetic(String),
+    OperationLimit,
+    // a 'return' that reached interpret without a function call to unwind to
+    TopLevelReturn,
+    AssertionFailed(String),
+    // a value of the right type that still can't be used, like an empty separator
+    InvalidValue(String),
+    // the filesystem refused a read or write, with the message describing why
+    Io(String),
+    // an operator the parser should never have produced
+    Internal(&'static str),
+}
+
+impl Display for RuntimeErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuntimeErrorKind::UndefinedVariable(name) => {
+                write!(f, "Undefined variable '{}'.", name)
+            }
+            RuntimeErrorKind::ConstantAssignment(name) => {
+                write!(f, "Cannot assign to constant '{}'.", name)
+            }
+            RuntimeErrorKind::ArityMismatch { expected, got } => {
+                if expected.start() == expected.end() {
+                    write!(
+                        f,
+                        "Expected {} arguments but got {}.",
+                        expected.start(),
+                        got
+                    )
+                } else {
+                    write!(
+                        f,
+                        "Expected {} to {} arguments but got {}.",
+                        expected.start(),
+                        expected.end(),
+                        got
+                    )
+                }
+            }
+            RuntimeErrorKind::NotCallable => write!(f, "Can only call functions and classes."),
+            RuntimeErrorKind::TypeMismatch(message) => write!(f, "{}", message),
+            RuntimeErrorKind::InvalidIndex { container } => {
+                write!(f, "{} index must be an integer.", container)
+            }
+            RuntimeErrorKind::IndexOutOfRange { container } => {
+                write!(f, "{} index out of range.", container)
+            }
+            RuntimeErrorKind::NilArithmetic(operator) => {
+                write!(
+                    f,
+                    "Cannot perform arithmetic with nil using '{}'.",
+                    operator
+                )
+            }
+            RuntimeErrorKind::OperationLimit => write!(f, "Operation limit exceeded."),
+            RuntimeErrorKind::TopLevelReturn => write!(f, "Can't return from top-level code."),
+            RuntimeErrorKind::AssertionFailed(message) => write!(f, "{}", message),
+            RuntimeErrorKind::InvalidValue(message) => write!(f, "{}", message),
+            RuntimeErrorKind::Io(message) => write!(f, "{}", message),
+            RuntimeErrorKind::Internal(message) => write!(f, "{}", message),
         }
     }
 }
 
 pub struct Return {
+    pub keyword: Token,
     pub value: Option<LiteralValue>,
 }
 
 impl Return {
-    pub fn new(value: Option<LiteralValue>) -> Return {
-        Return { value }
+    pub fn new(keyword: Token, value: Option<LiteralValue>) -> Return {
+        Return { keyword, value }
     }
 }
 
+// a call in return position, unwound to the nearest call_function so the stack doesn't grow
+pub struct TailCall {
+    pub keyword: Token,
+    pub function: LoxCallables,
+    pub paren: Token,
+    pub arguments: Vec<Option<LiteralValue>>,
+}
+
+impl TailCall {
+    pub fn new(
+        keyword: Token,
+        function: LoxCallables,
+        paren: Token,
+        arguments: Vec<Option<LiteralValue>>,
+    ) -> TailCall {
+        TailCall {
+            keyword,
+            function,
+            paren,
+            arguments,
+        }
+    }
+}
+
+// iterations a loop runs before it's checked for a condition that can never change
+const STALL_THRESHOLD: usize = 1_000_000;
+
 pub struct Interpreter {
     pub globals: Rc<Environment>,
     environment: Rc<Environment>,
+    // monotonic baseline for clockMillis
+    pub start_time: Instant,
+    // xorshift state behind random(), reset by seed()
+    pub random_state: u64,
+    // where eprint writes, swappable so script diagnostics can be captured
+    pub err: Box<dyn Write>,
+    // host callbacks around every statement, for step debugging or counting
+    pub before_statement: Option<Box<dyn FnMut(&Stmt)>>,
+    pub after_statement: Option<Box<dyn FnMut(&Stmt)>>,
+    // evaluations and executions left before the script is stopped, None for no limit
+    operations_left: Option<usize>,
 }
 
 impl Interpreter {
-    pub fn new() -> Interpreter {
+    // only the natives the capabilities allow are defined, so disabled ones are undefined variables
+    pub fn new(capabilities: Capabilities) -> Interpreter {
         let globals = Rc::new(Environment::new(None));
 
-        // native functions here
-        globals.define(
-            "clock".to_owned(),
-            Some(LiteralValue::LoxCallable(LoxCallables::LoxAnonymous(
-                Box::new(LoxAnonymous::new(
-                    |_interpreter, _arguments| {
-                        Ok(Some(LiteralValue::Number(
-                            SystemTime::now()
-                                .duration_since(UNIX_EPOCH)
-                                .unwrap()
-                                .as_secs_f64(),
-                        )))
-                    },
-                    || 0,
-                )),
-            ))),
-        );
+        natives::define(&globals, capabilities);
 
         let environment = Rc::clone(&globals);
         Interpreter {
             globals,
             environment,
+            start_time: Instant::now(),
+            random_state: natives::scramble_seed(
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_nanos() as u64,
+            ),
+            err: Box::new(io::stderr()),
+            before_statement: None,
+            after_statement: None,
+            operations_left: None,
         }
     }
 
+    // stops the script with a runtime error after `limit` expressions and statements,
+    // so untrusted code can't run forever
+    pub fn with_limit(capabilities: Capabilities, limit: usize) -> Interpreter {
+        let mut interpreter = Interpreter::new(capabilities);
+        interpreter.operations_left = Some(limit);
+        return interpreter;
+    }
+
     pub fn interpret_expr(&mut self, expression: Expr) {
         let value = self.evaluate(&Box::new(expression));
         if value.is_ok() {
@@ -90,28 +225,138 @@ impl Interpreter {
     }
 
     pub fn interpret(&mut self, statements: Vec<Stmt>) {
-        let mut error: Option<RuntimeExceptions> = None;
+        if let Err(error) = self.try_interpret(statements) {
+            runtime_error(error);
+        }
+    }
+
+    // like interpret, but hands back the error instead of reporting it
+    pub fn try_interpret(&mut self, statements: Vec<Stmt>) -> Result<(), RuntimeError> {
         for statement in statements {
-            let result = self.execute(&statement);
-            if result.is_err() {
-                error = result.err();
-                break;
+            // a return only gets here when there was no function to return from
+            match self.execute(&statement) {
+                Ok(()) => {}
+                Err(RuntimeExceptions::RuntimeError(error)) => return Err(error),
+                Err(RuntimeExceptions::Return(r#return)) => {
+                    return Err(RuntimeError::new(
+                        &r#return.keyword,
+                        RuntimeErrorKind::TopLevelReturn,
+                    ))
+                }
+                Err(RuntimeExceptions::TailCall(tail_call)) => {
+                    return Err(RuntimeError::new(
+                        &tail_call.keyword,
+                        RuntimeErrorKind::TopLevelReturn,
+                    ))
+                }
             }
         }
+        return Ok(());
+    }
 
-        if error.is_some() {
-            match error.unwrap() {
-                RuntimeExceptions::RuntimeError(run_error) => runtime_error(run_error),
-                _ => {}
+    // calls a function, then keeps calling whatever it tail calls until a value comes back
+    pub fn call_function(
+        &mut self,
+        function: LoxCallables,
+        paren: &Token,
+        arguments: Vec<Option<LiteralValue>>,
+    ) -> Result<Option<LiteralValue>, RuntimeExceptions> {
+        let mut function = function;
+        let mut paren = paren.clone();
+        let mut arguments = arguments;
+        loop {
+            if !function.arity().contains(&arguments.len()) {
+                return Err(RuntimeExceptions::RuntimeError(RuntimeError::new(
+                    &paren,
+                    RuntimeErrorKind::ArityMismatch {
+                        expected: function.arity(),
+                        got: arguments.len(),
+                    },
+                )));
             }
+
+            let result = function.call(self, &paren, arguments);
+            match result {
+                Err(RuntimeExceptions::Return(r#return)) => return Ok(r#return.value),
+                Err(RuntimeExceptions::TailCall(tail_call)) => {
+                    let tail_call = *tail_call;
+                    function = tail_call.function;
+                    paren = tail_call.paren;
+                    arguments = tail_call.arguments;
+                }
+                _ => return result,
+            }
+        }
+    }
+
+    fn evaluate_call(
+        &mut self,
+        call: &expr::Call,
+    ) -> Result<(LoxCallables, Vec<Option<LiteralValue>>), RuntimeExceptions> {
+        let callee = self.evaluate(&call.callee)?;
+
+        let mut arguments = Vec::new();
+        for argument in &call.arguments {
+            arguments.push(self.evaluate(&Box::new(argument))?);
         }
+
+        let function = match callee {
+            Some(LiteralValue::LoxCallable(callable)) => Ok(callable),
+            _ => Err(RuntimeExceptions::RuntimeError(RuntimeError::new(
+                &call.paren,
+                RuntimeErrorKind::NotCallable,
+            ))),
+        }?;
+
+        return Ok((function, arguments));
     }
 
     fn execute(&mut self, stmt: &Stmt) -> Result<(), RuntimeExceptions> {
-        stmt.accept(self)?;
+        self.spend_operation(stmt.span())?;
+        if let Some(hook) = self.before_statement.as_mut() {
+            hook(stmt);
+        }
+        let result = stmt.accept(self);
+        // runs even when the statement errored or returned, so begin and end always pair up
+        if let Some(hook) = self.after_statement.as_mut() {
+            hook(stmt);
+        }
+        result?;
         return Ok(());
     }
 
+    // names and stringified values of every scope from the innermost out to the globals,
+    // sorted by name within each scope
+    #[cfg(test)]
+    pub fn environment_snapshot(&self) -> Vec<Vec<(String, String)>> {
+        let mut scopes = Vec::new();
+        let mut environment = Some(&self.environment);
+        while environment.is_some() {
+            let current = environment.unwrap();
+            let mut scope: Vec<(String, String)> = current
+                .values
+                .borrow()
+                .iter()
+                .map(|(name, value)| (name.clone(), self.stringify(value)))
+                .collect();
+            scope.sort();
+            scopes.push(scope);
+            environment = current.enclosing.as_ref();
+        }
+        return scopes;
+    }
+
+    // captures the current scope chain so restore can rewind to it, see EnvSnapshot
+    #[cfg(test)]
+    pub fn snapshot(&self) -> crate::environment::EnvSnapshot {
+        return crate::environment::EnvSnapshot::capture(&self.environment);
+    }
+
+    #[cfg(test)]
+    pub fn restore(&mut self, snapshot: &crate::environment::EnvSnapshot) {
+        self.environment = snapshot.restore();
+    }
+
     pub fn execute_block(
         &mut self,
         statements: &Vec<Stmt>,
@@ -134,10 +379,73 @@ impl Interpreter {
         return error;
     }
 
+    // runs a desugared for loop, giving each iteration a fresh copy of the loop variable
+    // so closures created in the body capture that iteration's value
+    fn execute_rebinding_loop(&mut self, r#while: &stmt::While) -> Result<(), RuntimeExceptions> {
+        let name = r#while.loop_variable.as_ref().unwrap();
+        let enclosing = Rc::clone(&self.environment);
+
+        let mut first_iteration = true;
+        let mut iterations = 0;
+        loop {
+            let iteration = Rc::new(Environment::new(Some(&enclosing)));
+            iteration.define(name.lexeme.clone(), self.environment.get(name)?);
+            self.environment = iteration;
+
+            // the increment belongs to the next iteration's binding, not the one just captured
+            if !first_iteration && r#while.increment.is_some() {
+                self.evaluate(r#while.increment.as_ref().unwrap())?;
+            }
+            first_iteration = false;
+
+            let condition_value = self.evaluate(&r#while.condition)?;
+            if !self.is_truthy(&condition_value) {
+                return Ok(());
+            }
+            self.watch_loop(r#while, &mut iterations);
+            self.execute(&r#while.body)?;
+        }
+    }
+
+    // warns when a loop reaches STALL_THRESHOLD iterations and nothing in it can ever make its
+    // condition false, see loop_is_stuck
+    fn watch_loop(&self, r#while: &stmt::While, iterations: &mut usize) {
+        *iterations += 1;
+        if *iterations == STALL_THRESHOLD && loop_is_stuck(r#while) {
+            warning(
+                r#while.condition.span().0,
+                format!(
+                    "Loop condition hasn't changed after {} iterations; this may be an infinite loop.",
+                    iterations
+                ),
+            );
+        }
+    }
+
     fn evaluate(&mut self, expr: &Expr) -> Result<Option<LiteralValue>, RuntimeExceptions> {
+        self.spend_operation(expr.span())?;
         return expr.accept(self);
     }
 
+    fn spend_operation(&mut self, span: (usize, usize)) -> Result<(), RuntimeExceptions> {
+        match self.operations_left.as_mut() {
+            None => return Ok(()),
+            Some(0) => {}
+            Some(operations_left) => {
+                *operations_left -= 1;
+                return Ok(());
+            }
+        }
+
+        // nodes don't keep a token, so the error points at where the node starts
+        let (line, column) = source_location(span.0);
+        let token = Token::new(TokenType::EOF, String::new(), None, line, column, span.0);
+        return Err(RuntimeExceptions::RuntimeError(RuntimeError::new(
+            &token,
+            RuntimeErrorKind::OperationLimit,
+        )));
+    }
+
     fn is_truthy(&self, value: &Option<LiteralValue>) -> bool {
         if value.is_none() {
             return false;
@@ -148,11 +456,37 @@ impl Interpreter {
         }
     }
 
-    fn is_equal(&self, a: &Option<LiteralValue>, b: &Option<LiteralValue>) -> bool {
-        return a == b;
+    // values of different types are never equal, so 1 == "1" and nil == false are false.
+    // numbers follow IEEE 754 like the comparison operators do: NaN != NaN and 0 == -0,
+    // and an integer equals a float only when the float is exactly that whole number.
+    // strings and booleans compare by value, lists by identity
+    pub fn is_equal(&self, a: &Option<LiteralValue>, b: &Option<LiteralValue>) -> bool {
+        return match (a, b) {
+            (None, None) => true,
+            (Some(LiteralValue::Integer(a)), Some(LiteralValue::Integer(b))) => a == b,
+            (Some(LiteralValue::Integer(a)), Some(LiteralValue::Number(b)))
+            | (Some(LiteralValue::Number(b)), Some(LiteralValue::Integer(a))) => {
+                integer_equals_float(*a, *b)
+            }
+            (Some(LiteralValue::Number(a)), Some(LiteralValue::Number(b))) => a == b,
+            (Some(LiteralValue::String(a)), Some(LiteralValue::String(b))) => a == b,
+            (Some(LiteralValue::Boolean(a)), Some(LiteralValue::Boolean(b))) => a == b,
+            (Some(LiteralValue::List(a)), Some(LiteralValue::List(b))) => Rc::ptr_eq(a, b),
+            (Some(LiteralValue::LoxCallable(a)), Some(LiteralValue::LoxCallable(b))) => a == b,
+            _ => false,
+        };
     }
 
-    fn stringify(&self, value: &Option<LiteralValue>) -> String {
+    pub fn stringify(&self, value: &Option<LiteralValue>) -> String {
+        return self.stringify_visiting(value, &mut Vec::new());
+    }
+
+    // `visiting` holds the lists being printed, so one that contains itself shows as [...]
+    fn stringify_visiting(
+        &self,
+        value: &Option<LiteralValue>,
+        visiting: &mut Vec<Rc<RefCell<Vec<Option<LiteralValue>>>>>,
+    ) -> String {
         if value.is_none() {
             return "nil".to_string();
         }
@@ -164,24 +498,228 @@ impl Interpreter {
                 .to_string()
                 .trim_end_matches(".0")
                 .to_string(),
+            LiteralValue::Integer(value) => value.to_string(),
+            LiteralValue::List(values) => {
+                if visiting.iter().any(|list| Rc::ptr_eq(list, values)) {
+                    return "[...]".to_string();
+                }
+
+                visiting.push(Rc::clone(values));
+                let elements: Vec<String> = values
+                    .borrow()
+                    .iter()
+                    .map(|value| self.stringify_visiting(value, visiting))
+                    .collect();
+                visiting.pop();
+                format!("[{}]", elements.join(", "))
+            }
             _ => value.as_ref().unwrap().to_string(),
         };
     }
 
+    // like stringify, but produces valid json and errors on values json can't represent
+    pub fn jsonify(
+        &self,
+        token: &Token,
+        value: &Option<LiteralValue>,
+        visiting: &mut Vec<Rc<RefCell<Vec<Option<LiteralValue>>>>>,
+    ) -> Result<String, RuntimeExceptions> {
+        return match value {
+            None => Ok("null".to_string()),
+            Some(LiteralValue::Boolean(value)) => Ok(value.to_string()),
+            Some(LiteralValue::Number(number)) if number.is_finite() => Ok(self.stringify(value)),
+            Some(LiteralValue::Integer(_)) => Ok(self.stringify(value)),
+            Some(LiteralValue::String(value)) => Ok(json_quote(value)),
+            Some(LiteralValue::List(values)) => {
+                if visiting.iter().any(|list| Rc::ptr_eq(list, values)) {
+                    return Err(RuntimeExceptions::RuntimeError(RuntimeError::new(
+                        token,
+                        RuntimeErrorKind::InvalidValue(
+                            "Can't convert a list that contains itself to json.".to_string(),
+                        ),
+                    )));
+                }
+
+                visiting.push(Rc::clone(values));
+                let mut elements = Vec::new();
+                for element in values.borrow().iter() {
+                    elements.push(self.jsonify(token, element, visiting)?);
+                }
+                visiting.pop();
+                Ok(format!("[{}]", elements.join(",")))
+            }
+            Some(value) => Err(RuntimeExceptions::RuntimeError(RuntimeError::new(
+                token,
+                RuntimeErrorKind::InvalidValue(format!(
+                    "Can't convert '{}' to json.",
+                    self.stringify(&Some(value.clone()))
+                )),
+            ))),
+        };
+    }
+
     fn check_number_operand(
         &self,
         operator: &Token,
         operand: &Option<LiteralValue>,
     ) -> Result<f64, RuntimeExceptions> {
+        match number_cast(operand) {
+            Some(value) => return Ok(value),
+            _ => {
+                return Err(RuntimeExceptions::RuntimeError(RuntimeError::new(
+                    operator,
+                    RuntimeErrorKind::TypeMismatch("Operand must be a number."),
+                )))
+            }
+        }
+    }
+
+    fn check_integer_operand(
+        &self,
+        operator: &Token,
+        operand: &Option<LiteralValue>,
+    ) -> Result<i64, RuntimeExceptions> {
         match operand {
-            Some(LiteralValue::Number(value)) => return Ok(*value),
+            Some(LiteralValue::Integer(value)) => return Ok(*value),
+            Some(LiteralValue::Number(value)) if value.fract() == 0.0 => return Ok(*value as i64),
             _ => {
                 return Err(RuntimeExceptions::RuntimeError(RuntimeError::new(
                     operator,
-                    "Operand must be a number.",
+                    RuntimeErrorKind::TypeMismatch("Operand must be an integer."),
+                )))
+            }
+        }
+    }
+
+    fn check_integer_operands(
+        &self,
+        operator: &Token,
+        left: &Option<LiteralValue>,
+        right: &Option<LiteralValue>,
+    ) -> Result<(i64, i64), RuntimeExceptions> {
+        if let (Some(LiteralValue::Integer(lint)), Some(LiteralValue::Integer(rint))) =
+            (left, right)
+        {
+            return Ok((*lint, *rint));
+        }
+
+        let (lnumber, rnumber) = self
+            .check_number_operands(operator, left, right)
+            .map_err(|_| self.integer_operands_error(operator))?;
+        if lnumber.fract() != 0.0 || rnumber.fract() != 0.0 {
+            return Err(self.integer_operands_error(operator));
+        }
+        // 'as' saturates, so values beyond the i64 range are clamped rather than wrapped
+        return Ok((lnumber as i64, rnumber as i64));
+    }
+
+    fn integer_operands_error(&self, operator: &Token) -> RuntimeExceptions {
+        return RuntimeExceptions::RuntimeError(RuntimeError::new(
+            operator,
+            RuntimeErrorKind::TypeMismatch("Operands must be integers."),
+        ));
+    }
+
+    fn check_shift_amount(&self, operator: &Token, amount: i64) -> Result<u32, RuntimeExceptions> {
+        if !(0..64).contains(&amount) {
+            return Err(RuntimeExceptions::RuntimeError(RuntimeError::new(
+                operator,
+                RuntimeErrorKind::InvalidValue(
+                    "Shift amount must be between 0 and 63.".to_string(),
+                ),
+            )));
+        }
+        return Ok(amount as u32);
+    }
+
+    // negative bounds count from the end, like indexes. bounds past either end are clamped
+    fn check_slice_bound(
+        &self,
+        bracket: &Token,
+        bound: &Option<LiteralValue>,
+        len: usize,
+    ) -> Result<usize, RuntimeExceptions> {
+        let bound = match bound {
+            Some(LiteralValue::Integer(value)) => *value,
+            // 'as' saturates, so huge floats just clamp
+            Some(LiteralValue::Number(value)) if value.fract() == 0.0 => *value as i64,
+            _ => {
+                return Err(RuntimeExceptions::RuntimeError(RuntimeError::new(
+                    bracket,
+                    RuntimeErrorKind::TypeMismatch("Slice bounds must be integers."),
                 )))
             }
+        };
+        let len = len as i64;
+        let bound = if bound < 0 {
+            bound.saturating_add(len)
+        } else {
+            bound
+        };
+        return Ok(bound.clamp(0, len) as usize);
+    }
+
+    // integers stay integers until they overflow, anything involving a float is a float
+    pub fn arithmetic(
+        &self,
+        operator: &Token,
+        left: &Option<LiteralValue>,
+        right: &Option<LiteralValue>,
+        integer_op: fn(i64, i64) -> Option<i64>,
+        float_op: fn(f64, f64) -> f64,
+    ) -> Result<Option<LiteralValue>, RuntimeExceptions> {
+        if let (Some(LiteralValue::Integer(lint)), Some(LiteralValue::Integer(rint))) =
+            (left, right)
+        {
+            return match integer_op(*lint, *rint) {
+                Some(value) => Ok(Some(LiteralValue::Integer(value))),
+                None => Ok(Some(LiteralValue::Number(float_op(
+                    *lint as f64,
+                    *rint as f64,
+                )))),
+            };
         }
+
+        let (lnumber, rnumber) = self.check_number_operands(operator, left, right)?;
+        return Ok(Some(LiteralValue::Number(float_op(lnumber, rnumber))));
+    }
+
+    // integer division only stays an integer when it's exact, so 7 / 2 is still 3.5
+    fn divide(
+        &self,
+        operator: &Token,
+        left: &Option<LiteralValue>,
+        right: &Option<LiteralValue>,
+    ) -> Result<Option<LiteralValue>, RuntimeExceptions> {
+        if let (Some(LiteralValue::Integer(lint)), Some(LiteralValue::Integer(rint))) =
+            (left, right)
+        {
+            if lint.checked_rem(*rint) == Some(0) {
+                return Ok(Some(LiteralValue::Integer(lint / rint)));
+            }
+        }
+
+        let (lnumber, rnumber) = self.check_number_operands(operator, left, right)?;
+        return Ok(Some(LiteralValue::Number(lnumber / rnumber)));
+    }
+
+    // integers compare exactly, anything involving a float compares as floats
+    fn compare(
+        &self,
+        operator: &Token,
+        left: &Option<LiteralValue>,
+        right: &Option<LiteralValue>,
+        integer_op: fn(&i64, &i64) -> bool,
+        float_op: fn(&f64, &f64) -> bool,
+    ) -> Result<Option<LiteralValue>, RuntimeExceptions> {
+        if let (Some(LiteralValue::Integer(lint)), Some(LiteralValue::Integer(rint))) =
+            (left, right)
+        {
+            return Ok(Some(LiteralValue::Boolean(integer_op(lint, rint))));
+        }
+
+        let (lnumber, rnumber) = self.check_number_operands(operator, left, right)?;
+        return Ok(Some(LiteralValue::Boolean(float_op(&lnumber, &rnumber))));
     }
 
     fn check_number_operands(
@@ -197,7 +735,7 @@ impl Interpreter {
         }
         return Err(RuntimeExceptions::RuntimeError(RuntimeError::new(
             operator,
-            "Operands must be numbers.",
+            RuntimeErrorKind::TypeMismatch("Operands must be numbers."),
         )));
     }
 }
@@ -205,6 +743,24 @@ impl Interpreter {
 impl stmt::Visitor for Interpreter {
     type Output = Result<(), RuntimeExceptions>;
 
+    fn visit_assert(&mut self, assert: &stmt::Assert) -> Self::Output {
+        let condition_value = self.evaluate(&assert.condition)?;
+        if self.is_truthy(&condition_value) {
+            return Ok(());
+        }
+
+        let mut message = "Assertion failed.".to_string();
+        if assert.message.is_some() {
+            let message_value = self.evaluate(assert.message.as_ref().unwrap())?;
+            message = self.stringify(&message_value);
+        }
+
+        return Err(RuntimeExceptions::RuntimeError(RuntimeError::new(
+            &assert.keyword,
+            RuntimeErrorKind::AssertionFailed(message),
+        )));
+    }
+
     fn visit_block(&mut self, block: &stmt::Block) -> Self::Output {
         let result = self.execute_block(
             &block.statements,
@@ -213,11 +769,59 @@ impl stmt::Visitor for Interpreter {
         return result;
     }
 
+    fn visit_destructure(&mut self, destructure: &stmt::Destructure) -> Self::Output {
+        let value = self.evaluate(&destructure.initializer)?;
+        for (name, value) in destructure_list(&destructure.pattern, &value)? {
+            self.environment.define(name.lexeme.clone(), value);
+        }
+        return Ok(());
+    }
+
     fn visit_expression(&mut self, expression: &stmt::Expression) -> Self::Output {
         self.evaluate(&expression.expression)?;
         return Ok(());
     }
 
+    fn visit_for_each(&mut self, for_each: &stmt::ForEach) -> Self::Output {
+        let iterable = self.evaluate(&for_each.iterable)?;
+        let values = match iterable {
+            Some(LiteralValue::List(values)) => values,
+            // a string iterates over a snapshot of its characters
+            Some(LiteralValue::String(value)) => Rc::new(RefCell::new(
+                value
+                    .chars()
+                    .map(|char| Some(LiteralValue::String(char.to_string())))
+                    .collect(),
+            )),
+            _ => {
+                return Err(RuntimeExceptions::RuntimeError(RuntimeError::new(
+                    &for_each.keyword,
+                    RuntimeErrorKind::TypeMismatch("Can only iterate over lists and strings."),
+                )))
+            }
+        };
+
+        let previous = Rc::clone(&self.environment);
+        let mut result = Ok(());
+        // indexing each time, rather than holding a borrow, lets the body modify the list
+        let mut index = 0;
+        while index < values.borrow().len() {
+            let item = values.borrow().get(index).unwrap().clone();
+            let iteration = Rc::new(Environment::new(Some(&previous)));
+            iteration.define(for_each.name.lexeme.clone(), item);
+            self.environment = iteration;
+
+            result = self.execute(&for_each.body);
+            if result.is_err() {
+                break;
+            }
+            index += 1;
+        }
+        self.environment = previous;
+
+        return result;
+    }
+
     fn visit_function(&mut self, function: &stmt::Function) -> Self::Output {
         let value = Some(LiteralValue::LoxCallable(LoxCallables::LoxFunction(
             Box::new(LoxFunction::new(
@@ -245,13 +849,48 @@ impl stmt::Visitor for Interpreter {
         return Ok(());
     }
 
+    fn visit_repeat(&mut self, repeat: &stmt::Repeat) -> Self::Output {
+        let count = match self.evaluate(&repeat.count)? {
+            Some(LiteralValue::Integer(count)) if count >= 0 => count as u64,
+            Some(LiteralValue::Number(count)) if count >= 0.0 && count.fract() == 0.0 => {
+                count as u64
+            }
+            _ => {
+                return Err(RuntimeExceptions::RuntimeError(RuntimeError::new(
+                    &repeat.keyword,
+                    RuntimeErrorKind::InvalidValue(
+                        "Repeat count must be a non-negative integer.".to_string(),
+                    ),
+                )))
+            }
+        };
+
+        for _ in 0..count {
+            self.execute(&repeat.body)?;
+        }
+        return Ok(());
+    }
+
     fn visit_return(&mut self, r#return: &stmt::Return) -> Self::Output {
+        if let Some(Expr::Call(call)) = &r#return.value {
+            let (function, arguments) = self.evaluate_call(call)?;
+            return Err(RuntimeExceptions::TailCall(Box::new(TailCall::new(
+                r#return.keyword.clone(),
+                function,
+                call.paren.clone(),
+                arguments,
+            ))));
+        }
+
         let mut value = None;
         if r#return.value.is_some() {
             value = self.evaluate(r#return.value.as_ref().unwrap())?;
         }
 
-        return Err(RuntimeExceptions::Return(Return::new(value)));
+        return Err(RuntimeExceptions::Return(Box::new(Return::new(
+            r#return.keyword.clone(),
+            value,
+        ))));
     }
 
     fn visit_var(&mut self, var: &stmt::Var) -> Self::Output {
@@ -260,14 +899,31 @@ impl stmt::Visitor for Interpreter {
             value = self.evaluate(var.initializer.as_ref().unwrap())?;
         }
 
-        self.environment.define(var.name.lexeme.clone(), value);
+        if var.constant {
+            self.environment
+                .define_constant(var.name.lexeme.clone(), value);
+        } else {
+            self.environment.define(var.name.lexeme.clone(), value);
+        }
         return Ok(());
     }
 
     fn visit_while(&mut self, r#while: &stmt::While) -> Self::Output {
+        if r#while.loop_variable.is_some() {
+            let previous = Rc::clone(&self.environment);
+            let result = self.execute_rebinding_loop(r#while);
+            self.environment = previous;
+            return result;
+        }
+
+        let mut iterations = 0;
         let mut condition_value = self.evaluate(&r#while.condition)?;
         while self.is_truthy(&condition_value) {
+            self.watch_loop(r#while, &mut iterations);
             self.execute(&r#while.body)?;
+            if r#while.increment.is_some() {
+                self.evaluate(r#while.increment.as_ref().unwrap())?;
+            }
             condition_value = self.evaluate(&r#while.condition)?;
         }
 
@@ -288,29 +944,58 @@ impl expr::Visitor for Interpreter {
         let left = self.evaluate(&binary.left)?;
         let right = self.evaluate(&binary.right)?;
 
+        // a nil operand is usually a variable that was never assigned, so say that directly.
+        // only when the other side is a number too, `"a" + nil` is a plain type mismatch
+        let arithmetic = matches!(
+            binary.operator.r#type,
+            TokenType::PLUS | TokenType::MINUS | TokenType::STAR | TokenType::SLASH
+        );
+        let numeric_or_nil = |value: &Option<LiteralValue>| {
+            matches!(
+                value,
+                None | Some(LiteralValue::Integer(_)) | Some(LiteralValue::Number(_))
+            )
+        };
+        if arithmetic
+            && (left.is_none() || right.is_none())
+            && numeric_or_nil(&left)
+            && numeric_or_nil(&right)
+        {
+            return Err(RuntimeExceptions::RuntimeError(RuntimeError::new(
+                &binary.operator,
+                RuntimeErrorKind::NilArithmetic(binary.operator.lexeme.clone()),
+            )));
+        }
+
         match binary.operator.r#type {
             TokenType::MINUS => {
-                let (lnumber, rnumber) =
-                    self.check_number_operands(&binary.operator, &left, &right)?;
-                return Ok(Some(LiteralValue::Number(lnumber - rnumber)));
-            }
-            TokenType::SLASH => {
-                let (lnumber, rnumber) =
-                    self.check_number_operands(&binary.operator, &left, &right)?;
-                return Ok(Some(LiteralValue::Number(lnumber / rnumber)));
+                return self.arithmetic(
+                    &binary.operator,
+                    &left,
+                    &right,
+                    i64::checked_sub,
+                    |a, b| a - b,
+                );
             }
+            TokenType::SLASH => return self.divide(&binary.operator, &left, &right),
             TokenType::STAR => {
-                let (lnumber, rnumber) =
-                    self.check_number_operands(&binary.operator, &left, &right)?;
-                return Ok(Some(LiteralValue::Number(lnumber * rnumber)));
+                return self.arithmetic(
+                    &binary.operator,
+                    &left,
+                    &right,
+                    i64::checked_mul,
+                    |a, b| a * b,
+                );
             }
             TokenType::PLUS => {
-                let lnumber = number_cast(&left);
-                let rnumber = number_cast(&right);
-                if lnumber.is_some() && rnumber.is_some() {
-                    return Ok(Some(LiteralValue::Number(
-                        lnumber.unwrap() + rnumber.unwrap(),
-                    )));
+                if number_cast(&left).is_some() && number_cast(&right).is_some() {
+                    return self.arithmetic(
+                        &binary.operator,
+                        &left,
+                        &right,
+                        i64::checked_add,
+                        |a, b| a + b,
+                    );
                 }
 
                 let lstring = string_cast(&left);
@@ -321,30 +1006,56 @@ impl expr::Visitor for Interpreter {
                     )));
                 }
 
+                // a string with a number or boolean concatenates the other's display form
+                if lstring.is_some() && is_coercible(&right) {
+                    return Ok(Some(LiteralValue::String(
+                        lstring.unwrap() + self.stringify(&right).as_str(),
+                    )));
+                }
+                if rstring.is_some() && is_coercible(&left) {
+                    return Ok(Some(LiteralValue::String(
+                        self.stringify(&left) + rstring.unwrap().as_str(),
+                    )));
+                }
+
                 return Err(RuntimeExceptions::RuntimeError(RuntimeError::new(
                     &binary.operator,
-                    "Operands must be two numbers or two strings.",
+                    RuntimeErrorKind::TypeMismatch("Operands must be two numbers or two strings."),
                 )));
             }
             TokenType::GREATER => {
-                let (lnumber, rnumber) =
-                    self.check_number_operands(&binary.operator, &left, &right)?;
-                return Ok(Some(LiteralValue::Boolean(lnumber > rnumber)));
+                return self.compare(&binary.operator, &left, &right, i64::gt, f64::gt);
             }
             TokenType::GREATER_EQUAL => {
-                let (lnumber, rnumber) =
-                    self.check_number_operands(&binary.operator, &left, &right)?;
-                return Ok(Some(LiteralValue::Boolean(lnumber >= rnumber)));
+                return self.compare(&binary.operator, &left, &right, i64::ge, f64::ge);
             }
             TokenType::LESS => {
-                let (lnumber, rnumber) =
-                    self.check_number_operands(&binary.operator, &left, &right)?;
-                return Ok(Some(LiteralValue::Boolean(lnumber < rnumber)));
+                return self.compare(&binary.operator, &left, &right, i64::lt, f64::lt);
             }
             TokenType::LESS_EQUAL => {
-                let (lnumber, rnumber) =
-                    self.check_number_operands(&binary.operator, &left, &right)?;
-                return Ok(Some(LiteralValue::Boolean(lnumber <= rnumber)));
+                return self.compare(&binary.operator, &left, &right, i64::le, f64::le);
+            }
+            TokenType::AMPERSAND => {
+                let (lint, rint) = self.check_integer_operands(&binary.operator, &left, &right)?;
+                return Ok(Some(LiteralValue::Integer(lint & rint)));
+            }
+            TokenType::PIPE => {
+                let (lint, rint) = self.check_integer_operands(&binary.operator, &left, &right)?;
+                return Ok(Some(LiteralValue::Integer(lint | rint)));
+            }
+            TokenType::CARET => {
+                let (lint, rint) = self.check_integer_operands(&binary.operator, &left, &right)?;
+                return Ok(Some(LiteralValue::Integer(lint ^ rint)));
+            }
+            TokenType::LESS_LESS => {
+                let (lint, rint) = self.check_integer_operands(&binary.operator, &left, &right)?;
+                let shift = self.check_shift_amount(&binary.operator, rint)?;
+                return Ok(Some(LiteralValue::Integer(lint.wrapping_shl(shift))));
+            }
+            TokenType::GREATER_GREATER => {
+                let (lint, rint) = self.check_integer_operands(&binary.operator, &left, &right)?;
+                let shift = self.check_shift_amount(&binary.operator, rint)?;
+                return Ok(Some(LiteralValue::Integer(lint.wrapping_shr(shift))));
             }
             TokenType::BANG_EQUAL => {
                 return Ok(Some(LiteralValue::Boolean(!self.is_equal(&left, &right))))
@@ -355,48 +1066,73 @@ impl expr::Visitor for Interpreter {
             _ => {
                 return Err(RuntimeExceptions::RuntimeError(RuntimeError::new(
                     &binary.operator,
-                    "Invalid operator when evaluating binary!",
+                    RuntimeErrorKind::Internal("Invalid operator when evaluating binary!"),
                 )))
             }
         };
     }
 
+    fn visit_block(&mut self, block: &expr::Block) -> Self::Output {
+        let environment = Rc::new(Environment::new(Some(&self.environment)));
+        self.execute_block(&block.statements, Rc::clone(&environment))?;
+        if block.value.is_none() {
+            return Ok(None);
+        }
+
+        // the value is evaluated in the block's scope so it can see the block's variables
+        let previous = std::mem::replace(&mut self.environment, environment);
+        let value = self.evaluate(block.value.as_ref().unwrap());
+        self.environment = previous;
+        return value;
+    }
+
     fn visit_call(&mut self, call: &expr::Call) -> Self::Output {
-        let callee = self.evaluate(&call.callee)?;
+        let (function, arguments) = self.evaluate_call(call)?;
+        return self.call_function(function, &call.paren, arguments);
+    }
 
-        let mut arguments = Vec::new();
-        for argument in &call.arguments {
-            arguments.push(self.evaluate(&Box::new(argument))?);
+    fn visit_destructure(&mut self, destructure: &expr::Destructure) -> Self::Output {
+        let value = self.evaluate(&destructure.value)?;
+        for (name, element) in destructure_list(&destructure.pattern, &value)? {
+            self.environment.assign(name, element)?;
         }
+        return Ok(value);
+    }
 
-        let mut function = match callee {
-            Some(LiteralValue::LoxCallable(callable)) => Ok(callable),
-            _ => Err(RuntimeExceptions::RuntimeError(RuntimeError::new(
-                &call.paren,
-                "Can only call functions and classes.",
-            ))),
-        }?;
+    fn visit_grouping(&mut self, grouping: &expr::Grouping) -> Self::Output {
+        return self.evaluate(&grouping.expression);
+    }
 
-        if arguments.len() != function.arity() {
-            return Err(RuntimeExceptions::RuntimeError(RuntimeError::new(
-                &call.paren,
-                &format!(
-                    "Expected {} arguments but got {}.",
-                    function.arity(),
-                    arguments.len()
-                ),
-            )));
-        }
+    fn visit_index(&mut self, index: &expr::Index) -> Self::Output {
+        let object = self.evaluate(&index.object)?;
+        let index_value = self.evaluate(&index.index)?;
 
-        let result = function.call(self, arguments);
-        return match result {
-            Err(RuntimeExceptions::Return(r#return)) => Ok(r#return.value),
-            _ => result,
+        let values = match object {
+            Some(LiteralValue::List(values)) => values,
+            // strings index by character, not byte, and give back a one-character string
+            Some(LiteralValue::String(value)) => {
+                let chars: Vec<char> = value.chars().collect();
+                let position = index_cast(&index.bracket, &index_value, chars.len(), "String")?;
+                return Ok(Some(LiteralValue::String(chars[position].to_string())));
+            }
+            _ => {
+                return Err(RuntimeExceptions::RuntimeError(RuntimeError::new(
+                    &index.bracket,
+                    RuntimeErrorKind::TypeMismatch("Only lists and strings can be indexed."),
+                )))
+            }
         };
+        let values = values.borrow();
+        let position = index_cast(&index.bracket, &index_value, values.len(), "List")?;
+        return Ok(values.get(position).unwrap().clone());
     }
 
-    fn visit_grouping(&mut self, grouping: &expr::Grouping) -> Self::Output {
-        return self.evaluate(&grouping.expression);
+    fn visit_list(&mut self, list: &expr::List) -> Self::Output {
+        let mut values = Vec::new();
+        for element in &list.elements {
+            values.push(self.evaluate(element)?);
+        }
+        return Ok(Some(LiteralValue::List(Rc::new(RefCell::new(values)))));
     }
 
     fn visit_literal(&mut self, literal: &expr::Literal) -> Self::Output {
@@ -418,10 +1154,15 @@ impl expr::Visitor for Interpreter {
                     return Ok(left);
                 }
             }
+            TokenType::QUESTION_QUESTION => {
+                if left.is_some() {
+                    return Ok(left);
+                }
+            }
             _ => {
                 return Err(RuntimeExceptions::RuntimeError(RuntimeError::new(
                     &logical.operator,
-                    "Invalid operator when evaluating logical!",
+                    RuntimeErrorKind::Internal("Invalid operator when evaluating logical!"),
                 )))
             }
         }
@@ -429,19 +1170,81 @@ impl expr::Visitor for Interpreter {
         return self.evaluate(&logical.right);
     }
 
+    fn visit_postfix(&mut self, postfix: &expr::Postfix) -> Self::Output {
+        let old = self.environment.get(&postfix.name)?;
+        self.check_number_operand(&postfix.operator, &old)?;
+
+        let one = Some(LiteralValue::Integer(1));
+        let new = if postfix.operator.r#type == TokenType::PLUS_PLUS {
+            self.arithmetic(&postfix.operator, &old, &one, i64::checked_add, |a, b| {
+                a + b
+            })?
+        } else {
+            self.arithmetic(&postfix.operator, &old, &one, i64::checked_sub, |a, b| {
+                a - b
+            })?
+        };
+        self.environment.assign(&postfix.name, new)?;
+        return Ok(old);
+    }
+
+    fn visit_slice(&mut self, slice: &expr::Slice) -> Self::Output {
+        let object = self.evaluate(&slice.object)?;
+        let values = match object {
+            Some(LiteralValue::List(values)) => values,
+            _ => {
+                return Err(RuntimeExceptions::RuntimeError(RuntimeError::new(
+                    &slice.bracket,
+                    RuntimeErrorKind::TypeMismatch("Only lists can be sliced."),
+                )))
+            }
+        };
+        let len = values.borrow().len();
+
+        let mut start = 0;
+        if slice.start.is_some() {
+            let value = self.evaluate(slice.start.as_ref().unwrap())?;
+            start = self.check_slice_bound(&slice.bracket, &value, len)?;
+        }
+        let mut end = len;
+        if slice.end.is_some() {
+            let value = self.evaluate(slice.end.as_ref().unwrap())?;
+            end = self.check_slice_bound(&slice.bracket, &value, len)?;
+        }
+        if start > end {
+            return Err(RuntimeExceptions::RuntimeError(RuntimeError::new(
+                &slice.bracket,
+                RuntimeErrorKind::InvalidValue("Slice start can't be after its end.".to_string()),
+            )));
+        }
+
+        let sliced = values.borrow()[start..end].to_vec();
+        return Ok(Some(LiteralValue::List(Rc::new(RefCell::new(sliced)))));
+    }
+
     fn visit_unary(&mut self, unary: &expr::Unary) -> Self::Output {
         let right = self.evaluate(&unary.right)?;
 
         match unary.operator.r#type {
             TokenType::MINUS => {
+                if let Some(LiteralValue::Integer(integer)) = right {
+                    return match integer.checked_neg() {
+                        Some(value) => Ok(Some(LiteralValue::Integer(value))),
+                        None => Ok(Some(LiteralValue::Number(-(integer as f64)))),
+                    };
+                }
                 let number = self.check_number_operand(&unary.operator, &right)?;
                 return Ok(Some(LiteralValue::Number(-number)));
             }
             TokenType::BANG => return Ok(Some(LiteralValue::Boolean(!self.is_truthy(&right)))),
+            TokenType::TILDE => {
+                let integer = self.check_integer_operand(&unary.operator, &right)?;
+                return Ok(Some(LiteralValue::Integer(!integer)));
+            }
             _ => {
                 return Err(RuntimeExceptions::RuntimeError(RuntimeError::new(
                     &unary.operator,
-                    "Invalid operator when evaluating unary!",
+                    RuntimeErrorKind::Internal("Invalid operator when evaluating unary!"),
                 )))
             }
         }
@@ -452,16 +1255,794 @@ impl expr::Visitor for Interpreter {
     }
 }
 
+// pairs each name in a pattern with its element of the list, and the rest name, if any,
+// with a new list of the elements left over
+fn destructure_list<'a>(
+    pattern: &'a Pattern,
+    value: &Option<LiteralValue>,
+) -> Result<Vec<(&'a Token, Option<LiteralValue>)>, RuntimeExceptions> {
+    let values = match value {
+        Some(LiteralValue::List(values)) => values.borrow().clone(),
+        _ => {
+            return Err(RuntimeExceptions::RuntimeError(RuntimeError::new(
+                &pattern.bracket,
+                RuntimeErrorKind::TypeMismatch("Can only destructure lists."),
+            )))
+        }
+    };
+
+    let expected = pattern.names.len();
+    let fits = match pattern.rest {
+        Some(_) => values.len() >= expected,
+        None => values.len() == expected,
+    };
+    if !fits {
+        let at_least = if pattern.rest.is_some() {
+            "at least "
+        } else {
+            ""
+        };
+        return Err(RuntimeExceptions::RuntimeError(RuntimeError::new(
+            &pattern.bracket,
+            RuntimeErrorKind::InvalidValue(format!(
+                "Expected {}{} elements to destructure but got {}.",
+                at_least,
+                expected,
+                values.len()
+            )),
+        )));
+    }
+
+    let mut values = values.into_iter();
+    let mut bindings: Vec<(&Token, Option<LiteralValue>)> =
+        pattern.names.iter().zip(values.by_ref()).collect();
+    if let Some(rest) = &pattern.rest {
+        let rest_values = Rc::new(RefCell::new(values.collect()));
+        bindings.push((rest, Some(LiteralValue::List(rest_values))));
+    }
+    return Ok(bindings);
+}
+
+// `container` names what's being indexed in the error messages, like "List"
+// whether a loop's condition can never become false once it's true. that's the case when the
+// condition only combines literals and variables, and the body and increment never assign those
+// variables, call anything or return. calls are ruled out because a function could assign
+// the variables or change the contents of a list they hold
+fn loop_is_stuck(r#while: &stmt::While) -> bool {
+    let mut names = Vec::new();
+    if !condition_variables(&r#while.condition, &mut names) {
+        return false;
+    }
+    if let Some(increment) = r#while.increment.as_ref() {
+        if expr_may_change(increment, &names) {
+            return false;
+        }
+    }
+    return !stmt_may_change(&r#while.body, &names);
+}
+
+// collects the variables a condition reads, or returns false if it reads anything else
+fn condition_variables(expr: &Expr, names: &mut Vec<String>) -> bool {
+    return match expr {
+        Expr::Literal(_) => true,
+        Expr::Variable(variable) => {
+            names.push(variable.name.lexeme.clone());
+            true
+        }
+        Expr::Grouping(grouping) => condition_variables(&grouping.expression, names),
+        Expr::Unary(unary) => condition_variables(&unary.right, names),
+        Expr::Binary(binary) => {
+            condition_variables(&binary.left, names) && condition_variables(&binary.right, names)
+        }
+        Expr::Logical(logical) => {
+            condition_variables(&logical.left, names) && condition_variables(&logical.right, names)
+        }
+        _ => false,
+    };
+}
+
+// whether running the statement could assign one of the names, call a function or return.
+// declarations count as assignments, so a shadowing name is treated as a change too
+fn stmt_may_change(stmt: &Stmt, names: &[String]) -> bool {
+    let named = |token: &Token| names.contains(&token.lexeme);
+    return match stmt {
+        Stmt::Assert(assert) => {
+            expr_may_change(&assert.condition, names)
+                || assert
+                    .message
+                    .as_ref()
+                    .is_some_and(|message| expr_may_change(message, names))
+        }
+        Stmt::Block(block) => block
+            .statements
+            .iter()
+            .any(|statement| stmt_may_change(statement, names)),
+        Stmt::Destructure(destructure) => {
+            pattern_names(&destructure.pattern).any(named)
+                || expr_may_change(&destructure.initializer, names)
+        }
+        Stmt::Expression(expression) => expr_may_change(&expression.expression, names),
+        Stmt::ForEach(for_each) => {
+            named(&for_each.name)
+                || expr_may_change(&for_each.iterable, names)
+                || stmt_may_change(&for_each.body, names)
+        }
+        // declaring a function doesn't run it, but it does bind its name
+        Stmt::Function(function) => named(&function.name),
+        Stmt::If(r#if) => {
+            expr_may_change(&r#if.condition, names)
+                || stmt_may_change(&r#if.then_branch, names)
+                || r#if
+                    .else_branch
+                    .as_ref()
+                    .is_some_and(|else_branch| stmt_may_change(else_branch, names))
+        }
+        Stmt::Print(print) => expr_may_change(&print.expression, names),
+        Stmt::Repeat(repeat) => {
+            expr_may_change(&repeat.count, names) || stmt_may_change(&repeat.body, names)
+        }
+        Stmt::Return(_) => true,
+        Stmt::Var(var) => {
+            named(&var.name)
+                || var
+                    .initializer
+                    .as_ref()
+                    .is_some_and(|initializer| expr_may_change(initializer, names))
+        }
+        Stmt::While(r#while) => {
+            r#while.loop_variable.as_ref().is_some_and(named)
+                || expr_may_change(&r#while.condition, names)
+                || r#while
+                    .increment
+                    .as_ref()
+                    .is_some_and(|increment| expr_may_change(increment, names))
+                || stmt_may_change(&r#while.body, names)
+        }
+    };
+}
+
+fn expr_may_change(expr: &Expr, names: &[String]) -> bool {
+    let named = |token: &Token| names.contains(&token.lexeme);
+    return match expr {
+        Expr::Assign(assign) => named(&assign.name) || expr_may_change(&assign.value, names),
+        Expr::Binary(binary) => {
+            expr_may_change(&binary.left, names) || expr_may_change(&binary.right, names)
+        }
+        Expr::Block(block) => {
+            block
+                .statements
+                .iter()
+                .any(|statement| stmt_may_change(statement, names))
+                || block
+                    .value
+                    .as_ref()
+                    .is_some_and(|value| expr_may_change(value, names))
+        }
+        Expr::Call(_) => true,
+        Expr::Destructure(destructure) => {
+            pattern_names(&destructure.pattern).any(named)
+                || expr_may_change(&destructure.value, names)
+        }
+        Expr::Grouping(grouping) => expr_may_change(&grouping.expression, names),
+        Expr::Index(index) => {
+            expr_may_change(&index.object, names) || expr_may_change(&index.index, names)
+        }
+        Expr::List(list) => list
+            .elements
+            .iter()
+            .any(|element| expr_may_change(element, names)),
+        Expr::Literal(_) => false,
+        Expr::Logical(logical) => {
+            expr_may_change(&logical.left, names) || expr_may_change(&logical.right, names)
+        }
+        Expr::Postfix(postfix) => named(&postfix.name),
+        Expr::Slice(slice) => {
+            expr_may_change(&slice.object, names)
+                || slice
+                    .start
+                    .as_ref()
+                    .is_some_and(|start| expr_may_change(start, names))
+                || slice
+                    .end
+                    .as_ref()
+                    .is_some_and(|end| expr_may_change(end, names))
+        }
+        Expr::Unary(unary) => expr_may_change(&unary.right, names),
+        Expr::Variable(_) => false,
+    };
+}
+
+fn pattern_names(pattern: &Pattern) -> impl Iterator<Item = &Token> {
+    return pattern.names.iter().chain(pattern.rest.iter());
+}
+
+pub fn index_cast(
+    token: &Token,
+    value: &Option<LiteralValue>,
+    len: usize,
+    container: &'static str,
+) -> Result<usize, RuntimeExceptions> {
+    let index = match value {
+        Some(LiteralValue::Integer(index)) => *index as f64,
+        Some(LiteralValue::Number(index)) if index.fract() == 0.0 => *index,
+        _ => {
+            return Err(RuntimeExceptions::RuntimeError(RuntimeError::new(
+                token,
+                RuntimeErrorKind::InvalidIndex { container },
+            )))
+        }
+    };
+    // negative indices count back from the end, so -1 is the last element
+    let index = if index < 0.0 {
+        index + len as f64
+    } else {
+        index
+    };
+    if index < 0.0 || index >= len as f64 {
+        return Err(RuntimeExceptions::RuntimeError(RuntimeError::new(
+            token,
+            RuntimeErrorKind::IndexOutOfRange { container },
+        )));
+    }
+    return Ok(index as usize);
+}
+
+pub fn json_quote(value: &str) -> String {
+    let mut quoted = "\"".to_string();
+    for char in value.chars() {
+        match char {
+            '"' => quoted += "\\\"",
+            '\\' => quoted += "\\\\",
+            '\n' => quoted += "\\n",
+            '\r' => quoted += "\\r",
+            '\t' => quoted += "\\t",
+            char if (char as u32) < 0x20 => quoted += &format!("\\u{:04x}", char as u32),
+            char => quoted.push(char),
+        }
+    }
+    quoted.push('"');
+    return quoted;
+}
+
+// whether the float is a whole number that converts back to exactly this integer. `as` saturates,
+// so 2^63 has to be ruled out before it would pass for i64::MAX
+fn integer_equals_float(integer: i64, float: f64) -> bool {
+    return float.fract() == 0.0
+        && float >= i64::MIN as f64
+        && float < i64::MAX as f64
+        && float as i64 == integer;
+}
+
 fn number_cast(value: &Option<LiteralValue>) -> Option<f64> {
     return match value {
         Some(LiteralValue::Number(value)) => Some(*value),
+        Some(LiteralValue::Integer(value)) => Some(*value as f64),
         _ => None,
     };
 }
 
+fn is_coercible(value: &Option<LiteralValue>) -> bool {
+    return matches!(
+        value,
+        Some(LiteralValue::Number(_))
+            | Some(LiteralValue::Integer(_))
+            | Some(LiteralValue::Boolean(_))
+    );
+}
+
 fn string_cast(value: &Option<LiteralValue>) -> Option<String> {
     return match value {
         Some(LiteralValue::String(value)) => Some(value.clone()),
         _ => None,
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use super::{Interpreter, RuntimeError, RuntimeErrorKind};
+    use crate::{
+        environment::Environment,
+        natives::Capabilities,
+        parser::parse_program,
+        test_support::{global, result, run, run_error},
+        token::LiteralValue,
+    };
+
+    #[test]
+    fn passing_assertions_do_nothing() {
+        assert_eq!(result("assert(1 == 1); var result = \"ok\";"), "ok");
+    }
+
+    #[test]
+    fn failing_assertions_stop_with_their_message() {
+        assert_eq!(run_error("assert(1 == 2);"), "Assertion failed.");
+        assert_eq!(run_error("assert(1 == 2, \"nope\");"), "nope");
+    }
+
+    #[test]
+    fn closures_capture_each_iteration_of_a_for_loop() {
+        let source = "
+            var first;
+            var second;
+            for (var i = 0; i < 2; i = i + 1) {
+                fun f() { return i; }
+                if (i == 0) first = f; else second = f;
+            }
+            var result = first() + second() * 10;
+        ";
+        assert_eq!(result(source), "10");
+    }
+
+    #[test]
+    fn changes_in_the_body_carry_into_the_next_iteration() {
+        let source = "
+            var seen = 0;
+            for (var i = 0; i < 6; i = i + 1) {
+                seen = seen * 10 + i;
+                i = i + 1;
+            }
+            var result = seen;
+        ";
+        assert_eq!(result(source), "24");
+    }
+
+    #[test]
+    fn strings_concatenate_with_numbers_and_booleans() {
+        assert_eq!(result("var result = \"count: \" + 5;"), "count: 5");
+        assert_eq!(result("var result = 1.5 + \"a\";"), "1.5a");
+        assert_eq!(result("var result = \"a\" + true;"), "atrue");
+        assert_eq!(result("var result = 1 + 2;"), "3");
+        assert_eq!(
+            run_error("[] + \"a\";"),
+            "Operands must be two numbers or two strings."
+        );
+    }
+
+    #[test]
+    fn bitwise_operators_work_on_integers() {
+        assert_eq!(
+            result("var result = [5 & 3, 5 | 3, 5 ^ 3, ~5, 1 << 3, -16 >> 2];"),
+            "[1, 7, 6, -6, 8, -4]"
+        );
+        // whole floats are truncated to integers first
+        assert_eq!(result("var result = 5.0 & 3;"), "1");
+    }
+
+    #[test]
+    fn bitwise_operators_reject_other_operands() {
+        assert_eq!(run_error("5.5 & 1;"), "Operands must be integers.");
+        assert_eq!(run_error("\"a\" | 1;"), "Operands must be integers.");
+        assert_eq!(
+            run_error("1 << 64;"),
+            "Shift amount must be between 0 and 63."
+        );
+    }
+
+    #[test]
+    fn constants_can_be_read_but_not_reassigned() {
+        assert_eq!(result("const a = 1; var result = a;"), "1");
+        assert_eq!(
+            run_error("const a = 1; a = 2;"),
+            "Cannot assign to constant 'a'."
+        );
+    }
+
+    #[test]
+    fn slices_can_omit_either_bound() {
+        assert_eq!(
+            result("var l = [1, 2, 3, 4]; var result = [l[1:3], l[:2], l[2:], l[:]];"),
+            "[[2, 3], [1, 2], [3, 4], [1, 2, 3, 4]]"
+        );
+        assert_eq!(
+            run_error("[1, 2, 3, 4][3:1];"),
+            "Slice start can't be after its end."
+        );
+    }
+
+    #[test]
+    fn environment_snapshot_lists_scopes_innermost_first() {
+        let mut interpreter = run("var g = 1;").unwrap();
+        let outer = Rc::new(Environment::new(Some(&interpreter.globals)));
+        outer.define("a".to_string(), Some(LiteralValue::Number(2.0)));
+        let inner = Rc::new(Environment::new(Some(&outer)));
+        inner.define("c".to_string(), None);
+        inner.define("b".to_string(), Some(LiteralValue::String("x".to_string())));
+        interpreter.environment = inner;
+
+        let snapshot = interpreter.environment_snapshot();
+        let pair = |name: &str, value: &str| (name.to_string(), value.to_string());
+        assert_eq!(snapshot.len(), 3);
+        assert_eq!(snapshot[0], [pair("b", "x"), pair("c", "nil")]);
+        assert_eq!(snapshot[1], [pair("a", "2")]);
+        assert!(snapshot[2].contains(&pair("g", "1")));
+    }
+
+    #[test]
+    fn equality_follows_the_documented_rules() {
+        // integers and floats compare by value, and the two zeros are equal
+        assert_eq!(
+            result("var result = [1 == 1.0, 0.0 == -0.0, 1 == 1.5];"),
+            "[true, true, false]"
+        );
+        // values of different types are never equal, nil included
+        assert_eq!(
+            result("var result = [\"1\" == 1, nil == false, nil == nil];"),
+            "[false, false, true]"
+        );
+        // NaN isn't equal to itself, and lists are equal only to themselves
+        assert_eq!(
+            result("var n = 0.0 / 0.0; var l = [1]; var result = [n == n, l == l, [1] == [1]];"),
+            "[false, true, false]"
+        );
+    }
+
+    #[test]
+    fn nil_coalescing_only_replaces_nil() {
+        assert_eq!(result("var result = false ?? 1;"), "false");
+        assert_eq!(result("var result = false or 1;"), "1");
+        assert_eq!(result("var result = nil ?? 1;"), "1");
+    }
+
+    #[test]
+    fn integer_overflow_falls_back_to_floats() {
+        let source = "
+            var a = 9223372036854775807 + 1;
+            var b = -9223372036854775807 - 2;
+            var c = 9223372036854775807 * 2;
+            var d = -(-9223372036854775807 - 1);
+        ";
+        let interpreter = run(source).unwrap();
+        assert_eq!(
+            global(&interpreter, "a"),
+            Some(LiteralValue::Number(9223372036854775808.0))
+        );
+        assert_eq!(
+            global(&interpreter, "b"),
+            Some(LiteralValue::Number(-9223372036854775809.0))
+        );
+        assert_eq!(
+            global(&interpreter, "c"),
+            Some(LiteralValue::Number(18446744073709551614.0))
+        );
+        assert_eq!(
+            global(&interpreter, "d"),
+            Some(LiteralValue::Number(9223372036854775808.0))
+        );
+    }
+
+    #[test]
+    fn integers_only_equal_floats_of_exactly_the_same_value() {
+        assert_eq!(result("var result = 2 == 2.0;"), "true");
+        assert_eq!(result("var result = 2.0 == 2;"), "true");
+        assert_eq!(result("var result = 2 == 2.5;"), "false");
+        // converted to a float, 9007199254740993 would round to 9007199254740992.0
+        assert_eq!(
+            result("var result = 9007199254740993 == 9007199254740992.0;"),
+            "false"
+        );
+        assert_eq!(
+            result("var result = 9223372036854775807 == 9223372036854775808.0;"),
+            "false"
+        );
+    }
+
+    #[test]
+    fn mixed_arithmetic_gives_floats() {
+        let interpreter = run("var a = 2 * 3; var b = 2 * 3.0; var c = 7 / 2;").unwrap();
+        assert_eq!(global(&interpreter, "a"), Some(LiteralValue::Integer(6)));
+        assert_eq!(global(&interpreter, "b"), Some(LiteralValue::Number(6.0)));
+        assert_eq!(global(&interpreter, "c"), Some(LiteralValue::Number(3.5)));
+        // a float result past the integer range is still a float, not an overflow
+        assert_eq!(
+            result("var result = 9999999999.0 * 9999999999.0;"),
+            "9.999999998e19"
+        );
+    }
+
+    #[test]
+    fn blocks_evaluate_to_their_trailing_expression() {
+        assert_eq!(result("var result = { var x = 2; x * 3 };"), "6");
+        assert_eq!(result("var result = { var y = 1; };"), "nil");
+        // a block in statement position is still a statement
+        assert_eq!(result("var result = 1; { result = 2; }"), "2");
+    }
+
+    #[test]
+    fn logical_assignment_skips_the_right_side_when_short_circuited() {
+        let source = "
+            var calls = 0;
+            fun f() { calls = calls + 1; return 5; }
+            var a = false;
+            a and= f();
+            var b = true;
+            b or= f();
+            var result = [a, b, calls];
+        ";
+        assert_eq!(result(source), "[false, true, 0]");
+    }
+
+    #[test]
+    fn logical_assignment_assigns_the_right_side_otherwise() {
+        let source = "
+            fun f() { return 5; }
+            var c = true;
+            c and= f();
+            var d = nil;
+            d or= f();
+            var result = [c, d];
+        ";
+        assert_eq!(result(source), "[5, 5]");
+    }
+
+    #[test]
+    fn statement_hooks_see_every_statement_executed() {
+        let before = Rc::new(Cell::new(0));
+        let after = Rc::new(Cell::new(0));
+        let mut interpreter = Interpreter::new(Capabilities::new());
+        let counter = Rc::clone(&before);
+        interpreter.before_statement = Some(Box::new(move |_| counter.set(counter.get() + 1)));
+        let counter = Rc::clone(&after);
+        interpreter.after_statement = Some(Box::new(move |_| counter.set(counter.get() + 1)));
+
+        // the var, the if, its block and the assignment inside it
+        let statements = parse_program("var a = 1; if (a > 0) { a = 2; }").unwrap();
+        interpreter.try_interpret(statements).unwrap();
+        assert_eq!(before.get(), 4);
+        assert_eq!(after.get(), 4);
+    }
+
+    fn error_kind(source: &str) -> RuntimeErrorKind {
+        return match run(source) {
+            Ok(_) => panic!("expected a runtime error from {:?}", source),
+            Err(error) => error.kind,
+        };
+    }
+
+    #[test]
+    fn runtime_errors_carry_a_matchable_kind() {
+        assert!(matches!(
+            error_kind("print missing;"),
+            RuntimeErrorKind::UndefinedVariable(name) if name == "missing"
+        ));
+        assert!(matches!(
+            error_kind("fun f(a) {} f();"),
+            RuntimeErrorKind::ArityMismatch { expected, got: 0 } if expected == (1..=1)
+        ));
+        assert!(matches!(error_kind("1();"), RuntimeErrorKind::NotCallable));
+        assert!(matches!(
+            error_kind("-\"a\";"),
+            RuntimeErrorKind::TypeMismatch(_)
+        ));
+        assert!(matches!(
+            error_kind("[1][5];"),
+            RuntimeErrorKind::IndexOutOfRange { container: "List" }
+        ));
+    }
+
+    #[test]
+    fn postfix_operators_yield_the_old_value() {
+        assert_eq!(
+            result("var i = 1; var a = i++; var b = i--; var result = [a, b, i];"),
+            "[1, 2, 1]"
+        );
+        assert_eq!(
+            run_error("var s = \"a\"; s++;"),
+            "Operand must be a number."
+        );
+    }
+
+    #[test]
+    fn strings_index_by_character() {
+        assert_eq!(
+            result("var result = [\"abc\"[0], \"abc\"[-1], \"héllo\"[1], \"☕🎉\"[1]];"),
+            "[a, c, é, 🎉]"
+        );
+        assert_eq!(run_error("\"abc\"[3];"), "String index out of range.");
+    }
+
+    #[test]
+    fn for_in_iterates_lists_and_strings() {
+        assert_eq!(
+            result("var result = []; for (x in [1, 2]) push(result, x * 10);"),
+            "[10, 20]"
+        );
+        assert_eq!(
+            result("var result = []; for (c in \"ab\") push(result, c);"),
+            "[a, b]"
+        );
+        assert_eq!(
+            run_error("for (x in 5) print x;"),
+            "Can only iterate over lists and strings."
+        );
+    }
+
+    #[test]
+    fn functions_return_their_trailing_expression() {
+        let source = "
+            fun implicit() { 1 + 1 }
+            fun explicit() { return 3; }
+            fun nothing() { var x = 1; }
+            fun either(early) { if (early) return 1; 2 }
+            var result = [implicit(), explicit(), nothing(), either(true), either(false)];
+        ";
+        assert_eq!(result(source), "[2, 3, nil, 1, 2]");
+    }
+
+    fn run_in(interpreter: &mut Interpreter, source: &str) -> Result<(), RuntimeError> {
+        return interpreter.try_interpret(parse_program(source).unwrap());
+    }
+
+    #[test]
+    fn restoring_a_snapshot_rewinds_variables() {
+        let mut interpreter = run("var a = 1;").unwrap();
+        let snapshot = interpreter.snapshot();
+        run_in(&mut interpreter, "a = 2; var b = 3;").unwrap();
+
+        interpreter.restore(&snapshot);
+        assert_eq!(global(&interpreter, "a"), Some(LiteralValue::Integer(1)));
+        assert!(run_in(&mut interpreter, "b;").is_err());
+    }
+
+    #[test]
+    fn restoring_a_snapshot_keeps_changes_made_inside_lists() {
+        let mut interpreter = run("var kept = [1]; var rebound = [1];").unwrap();
+        let snapshot = interpreter.snapshot();
+        run_in(&mut interpreter, "push(kept, 2); rebound = [2];").unwrap();
+
+        interpreter.restore(&snapshot);
+        // only the bindings rewind, the list they point at is the same one push changed
+        assert_eq!(
+            interpreter.stringify(&global(&interpreter, "kept")),
+            "[1, 2]"
+        );
+        assert_eq!(
+            interpreter.stringify(&global(&interpreter, "rebound")),
+            "[1]"
+        );
+    }
+
+    #[test]
+    fn a_top_level_return_is_an_error() {
+        let mut interpreter = run("var a = 1;").unwrap();
+        let error = run_in(&mut interpreter, "a = 2; return a; a = 3;").unwrap_err();
+        assert!(matches!(error.kind, RuntimeErrorKind::TopLevelReturn));
+        assert_eq!(error.token.lexeme, "return");
+        // nothing after the return runs
+        assert_eq!(global(&interpreter, "a"), Some(LiteralValue::Integer(2)));
+        // a tail call in return position is caught the same way
+        let error = run_in(&mut interpreter, "return clock();").unwrap_err();
+        assert!(matches!(error.kind, RuntimeErrorKind::TopLevelReturn));
+    }
+
+    #[test]
+    fn lists_that_contain_themselves_print_safely() {
+        assert_eq!(
+            result("var result = [1]; push(result, result);"),
+            "[1, [...]]"
+        );
+        // a list that's only repeated, not nested in itself, prints in full
+        assert_eq!(result("var a = [1]; var result = [a, a];"), "[[1], [1]]");
+    }
+
+    #[test]
+    fn repeat_runs_its_body_count_times() {
+        assert_eq!(
+            result("var result = 0; repeat (0) result = result + 1;"),
+            "0"
+        );
+        assert_eq!(
+            result("var result = 0; repeat (3) result = result + 1;"),
+            "3"
+        );
+        let message = "Repeat count must be a non-negative integer.";
+        assert_eq!(run_error("repeat (1.5) print 1;"), message);
+        assert_eq!(run_error("repeat (-1) print 1;"), message);
+    }
+
+    #[test]
+    fn destructuring_binds_exact_and_rest_patterns() {
+        assert_eq!(
+            result(
+                "var [a, b] = [1, 2]; var [c, ...rest] = [1, 2, 3]; var result = [a, b, c, rest];"
+            ),
+            "[1, 2, 1, [2, 3]]"
+        );
+        assert_eq!(
+            result("var a = 1; var b = 2; [a, b] = [b, a]; var result = [a, b];"),
+            "[2, 1]"
+        );
+    }
+
+    #[test]
+    fn destructuring_rejects_mismatches() {
+        assert_eq!(
+            run_error("var [a, b] = [1];"),
+            "Expected 2 elements to destructure but got 1."
+        );
+        assert_eq!(run_error("var [a, b] = 1;"), "Can only destructure lists.");
+    }
+
+    #[test]
+    fn negative_indexes_count_from_the_end() {
+        assert_eq!(result("var result = [1, 2, 3][-1];"), "3");
+        assert_eq!(result("var result = [1, 2, 3][-3];"), "1");
+        assert_eq!(run_error("[1, 2, 3][-4];"), "List index out of range.");
+    }
+
+    #[test]
+    fn fractional_indexes_are_rejected() {
+        assert_eq!(result("var result = [1, 2, 3][1.0];"), "2");
+        assert_eq!(
+            run_error("[1, 2, 3][1.5];"),
+            "List index must be an integer."
+        );
+    }
+
+    #[test]
+    fn negative_slice_bounds_count_from_the_end() {
+        assert_eq!(result("var result = [1, 2, 3, 4][-2:];"), "[3, 4]");
+        assert_eq!(result("var result = [1, 2, 3, 4][:-1];"), "[1, 2, 3]");
+        assert_eq!(result("var result = [1, 2, 3, 4][-10:2];"), "[1, 2]");
+    }
+
+    #[test]
+    fn numeric_arithmetic_with_nil_names_the_nil() {
+        assert_eq!(
+            run_error("nil + 1;"),
+            "Cannot perform arithmetic with nil using '+'."
+        );
+        assert_eq!(
+            run_error("1 - nil;"),
+            "Cannot perform arithmetic with nil using '-'."
+        );
+        assert_eq!(
+            run_error("nil * nil;"),
+            "Cannot perform arithmetic with nil using '*'."
+        );
+    }
+
+    #[test]
+    fn other_operands_with_nil_keep_the_type_error() {
+        assert_eq!(
+            run_error("\"a\" + nil;"),
+            "Operands must be two numbers or two strings."
+        );
+        assert_eq!(run_error("true - nil;"), "Operands must be numbers.");
+    }
+
+    #[test]
+    fn for_loop_counters_stay_exact_past_float_precision() {
+        let source = "
+            var count = 0;
+            for (var i = 9007199254740990; i < 9007199254740995; i = i + 1) count = count + 1;
+            var result = count;
+        ";
+        assert_eq!(result(source), "5");
+    }
+
+    fn stuck(source: &str) -> bool {
+        let statements = parse_program(source).unwrap();
+        return match statements.last() {
+            Some(crate::stmt::Stmt::While(r#while)) => super::loop_is_stuck(r#while),
+            statement => panic!("expected a while loop, got {:?}", statement),
+        };
+    }
+
+    #[test]
+    fn loops_are_only_stuck_when_nothing_can_change_their_condition() {
+        assert!(stuck("while (!done) n = n + 1;"));
+        assert!(stuck("while (true) print 1;"));
+        assert!(!stuck(
+            "while (!done) { n = n + 1; if (n > 10) done = true; }"
+        ));
+        assert!(!stuck("while (i < 10) i++;"));
+        // push could change what the list holds, and so could any other call
+        assert!(!stuck("while (xs == ys) push(xs, 1);"));
+        assert!(!stuck("while (!done) tick();"));
+        assert!(!stuck("while (n < len(xs)) n;"));
+        // a return can leave the loop without the condition changing
+        assert!(!stuck("while (true) { n = n + 1; if (n > 10) return n; }"));
+    }
+}
@@ -1,19 +1,32 @@
-use std::{fmt::Display, rc::Rc};
+use std::{
+    fmt::Display,
+    ops::RangeInclusive,
+    rc::Rc,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
 use crate::{
     environment::Environment,
     interpreter::{Interpreter, RuntimeExceptions},
     stmt::{self},
-    token::LiteralValue,
+    token::{LiteralValue, Token},
 };
 
+pub type NativeCall = fn(
+    &mut Interpreter,
+    &Token,
+    Vec<Option<LiteralValue>>,
+) -> Result<Option<LiteralValue>, RuntimeExceptions>;
+
 pub trait LoxCallable {
     fn call(
         &mut self,
         interpreter: &mut Interpreter,
+        paren: &Token,
         arguments: Vec<Option<LiteralValue>>,
     ) -> Result<Option<LiteralValue>, RuntimeExceptions>;
-    fn arity(&self) -> usize;
+    // how many arguments it accepts, a single count unless some are optional
+    fn arity(&self) -> RangeInclusive<usize>;
 }
 
 #[derive(Clone, PartialEq, Debug)]
@@ -37,15 +50,16 @@ impl LoxCallable for LoxCallables {
     fn call(
         &mut self,
         interpreter: &mut Interpreter,
+        paren: &Token,
         arguments: Vec<Option<LiteralValue>>,
     ) -> Result<Option<LiteralValue>, RuntimeExceptions> {
         match self {
-            LoxCallables::LoxFunction(value) => value.call(interpreter, arguments),
-            LoxCallables::LoxAnonymous(value) => value.call(interpreter, arguments),
+            LoxCallables::LoxFunction(value) => value.call(interpreter, paren, arguments),
+            LoxCallables::LoxAnonymous(value) => value.call(interpreter, paren, arguments),
         }
     }
 
-    fn arity(&self) -> usize {
+    fn arity(&self) -> RangeInclusive<usize> {
         match self {
             LoxCallables::LoxFunction(value) => value.arity(),
             LoxCallables::LoxAnonymous(value) => value.arity(),
@@ -53,47 +67,54 @@ impl LoxCallable for LoxCallables {
     }
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, Debug)]
 pub struct LoxAnonymous {
+    // the global it's defined as, which is what identifies it
+    name: &'static str,
     // maybe call this native function if it doesn't get reused
-    call_ref: fn(
-        &mut Interpreter,
-        Vec<Option<LiteralValue>>,
-    ) -> Result<Option<LiteralValue>, RuntimeExceptions>,
-    arity_ref: fn() -> usize,
+    call_ref: NativeCall,
+    arity: RangeInclusive<usize>,
 }
 
 impl LoxAnonymous {
-    pub fn new(
-        call: fn(
-            &mut Interpreter,
-            Vec<Option<LiteralValue>>,
-        ) -> Result<Option<LiteralValue>, RuntimeExceptions>,
-        arity: fn() -> usize,
-    ) -> LoxAnonymous {
+    pub fn new(name: &'static str, call: NativeCall, arity: RangeInclusive<usize>) -> LoxAnonymous {
         LoxAnonymous {
+            name,
             call_ref: call,
-            arity_ref: arity,
+            arity,
         }
     }
 }
 
+// function pointers aren't a reliable identity, the same function can have several addresses
+impl PartialEq for LoxAnonymous {
+    fn eq(&self, other: &Self) -> bool {
+        return self.name == other.name;
+    }
+}
+
 impl LoxCallable for LoxAnonymous {
     fn call(
         &mut self,
         interpreter: &mut Interpreter,
+        paren: &Token,
         arguments: Vec<Option<LiteralValue>>,
     ) -> Result<Option<LiteralValue>, RuntimeExceptions> {
-        (self.call_ref)(interpreter, arguments)
+        (self.call_ref)(interpreter, paren, arguments)
     }
 
-    fn arity(&self) -> usize {
-        (self.arity_ref)()
+    fn arity(&self) -> RangeInclusive<usize> {
+        self.arity.clone()
     }
 }
 
-#[derive(Clone, PartialEq, Debug)]
+// hands out LoxFunction ids
+static NEXT_FUNCTION_ID: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Clone, Debug)]
 pub struct LoxFunction {
+    // unique per declaration executed, and shared by copies of the same function value
+    id: usize,
     declaration: stmt::Function,
     closure: Rc<Environment>,
 }
@@ -101,16 +122,26 @@ pub struct LoxFunction {
 impl LoxFunction {
     pub fn new(declaration: stmt::Function, closure: Rc<Environment>) -> LoxFunction {
         LoxFunction {
+            id: NEXT_FUNCTION_ID.fetch_add(1, Ordering::Relaxed),
             declaration,
             closure,
         }
     }
 }
 
+// functions are equal only if they're the same function, never because two closures look alike.
+// comparing closures structurally would also recurse forever through a recursive function's scope
+impl PartialEq for LoxFunction {
+    fn eq(&self, other: &Self) -> bool {
+        return self.id == other.id;
+    }
+}
+
 impl LoxCallable for LoxFunction {
     fn call(
         &mut self,
         interpreter: &mut Interpreter,
+        _paren: &Token,
         arguments: Vec<Option<LiteralValue>>,
     ) -> Result<Option<LiteralValue>, RuntimeExceptions> {
         let environment = Rc::new(Environment::new(Some(&self.closure)));
@@ -126,7 +157,31 @@ impl LoxCallable for LoxFunction {
             .map(|_| None); // convert Ok from type '()' to 'Option<Literal>'
     }
 
-    fn arity(&self) -> usize {
-        self.declaration.params.len()
+    fn arity(&self) -> RangeInclusive<usize> {
+        let params = self.declaration.params.len();
+        params..=params
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::result;
+
+    #[test]
+    fn functions_are_equal_only_to_themselves() {
+        let source = "
+            fun make() { fun inner() { return 1; } return inner; }
+            var f = make();
+            var g = make();
+            var h = f;
+            var result = [f == f, f == h, f == g];
+        ";
+        assert_eq!(result(source), "[true, true, false]");
+    }
+
+    #[test]
+    fn natives_are_compared_by_name() {
+        let source = "var c = clock; var result = [c == clock, clock == len];";
+        assert_eq!(result(source), "[true, false]");
     }
 }
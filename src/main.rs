@@ -1,13 +1,17 @@
 use std::env;
 use std::fs;
 use std::io::{self, Write};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
 
 use ast_printer::AstPrinter;
 use expr::Expr;
 use interpreter::Interpreter;
 use interpreter::RuntimeError;
+use natives::Capabilities;
 use parser::Parser;
-use scanner::Scanner;
+use scanner::{line_column, next_column, Scanner, DEFAULT_TAB_WIDTH};
+use source_formatter::SourceFormatter;
 use stmt::Stmt;
 use token::Token;
 use token_type::TokenType;
@@ -17,52 +21,196 @@ mod environment;
 mod expr;
 mod interpreter;
 mod lox_callables;
+mod natives;
 mod parser;
+mod prelude;
 mod scanner;
+mod source_formatter;
 mod stmt;
+#[cfg(test)]
+mod test_support;
 mod token;
 mod token_type;
 
 static mut HAD_ERROR: bool = false;
 static mut HAD_RUNTIME_ERROR: bool = false;
+static mut ERROR_COUNT: usize = 0;
+static mut MAX_ERRORS: usize = 20;
+static mut TAB_WIDTH: usize = DEFAULT_TAB_WIDTH;
+// set by --eval, where the argument after the command is the source itself rather than a path
+static mut EVAL: bool = false;
+// the file being run, kept so errors can quote the line they point at
+static SOURCE: OnceLock<String> = OnceLock::new();
 
-pub fn error(line: u64, message: String) {
-    report(line, "".to_string(), message);
+pub fn error(line: u64, column: usize, message: String) {
+    report(line, column, "".to_string(), message);
 }
 
-fn report(line: u64, r#where: String, message: String) {
+// tokens without a column fall back to just the line, and synthetic ones have neither
+fn location(line: u64, column: usize) -> String {
+    if line == 0 {
+        return "generated code".to_string();
+    }
+    if column == 0 {
+        return format!("line {}", line);
+    }
+    return format!("line {}:{}", line, column);
+}
+
+// the offending source line with a caret under the column, or nothing if there's no column
+fn snippet(line: u64, column: usize) -> String {
+    // split the way the scanner counts lines, so a lone '\r' also ends one
+    let source_line = SOURCE.get().and_then(|source| {
+        source
+            .replace("\r\n", "\n")
+            .split(['\n', '\r'])
+            .nth(line.saturating_sub(1) as usize)
+            .map(str::to_string)
+    });
+    if column == 0 || source_line.is_none() {
+        return String::new();
+    }
+
+    let source_line = source_line.unwrap();
+    // keep tabs so the caret lines up however wide the terminal renders them
+    let mut padding = String::new();
+    let mut current = 1;
+    for char in source_line.chars() {
+        if current >= column {
+            break;
+        }
+        padding.push(if char == '\t' { '\t' } else { ' ' });
+        current = next_column(current, char, unsafe { TAB_WIDTH });
+    }
+    return format!("\n    {}\n    {}^", source_line, padding);
+}
+
+fn report(line: u64, column: usize, r#where: String, message: String) {
     unsafe { HAD_ERROR = true };
-    eprintln!("[line {}] Error{}: {}", line, r#where, message);
+    // scanner and parser errors share one budget, so a badly broken file can't flood stderr
+    if unsafe { ERROR_COUNT } >= unsafe { MAX_ERRORS } {
+        eprintln!("too many errors; aborting.");
+        std::process::exit(65);
+    }
+    unsafe { ERROR_COUNT += 1 };
+    eprintln!(
+        "[{}] Error{}: {}{}",
+        location(line, column),
+        r#where,
+        message,
+        snippet(line, column)
+    );
 }
 
 pub fn error_token(token: &Token, message: String) {
     if token.r#type == TokenType::EOF {
-        report(token.line, " at end".to_string(), message);
+        report(token.line, token.column, " at end".to_string(), message);
     } else {
-        report(token.line, format!(" at '{}'", token.lexeme), message);
+        report(
+            token.line,
+            token.column,
+            format!(" at '{}'", token.lexeme),
+            message,
+        );
     }
 }
 
+// line and column of a byte offset in the file being run, for errors raised from a span
+pub fn source_location(offset: usize) -> (u64, usize) {
+    return match SOURCE.get() {
+        Some(source) => line_column(source, offset, unsafe { TAB_WIDTH }),
+        None => (0, 0),
+    };
+}
+
+// warnings don't count against the error budget or change the exit code
+pub fn warning(offset: usize, message: String) {
+    let (line, column) = source_location(offset);
+    eprintln!(
+        "[{}] Warning: {}{}",
+        location(line, column),
+        message,
+        snippet(line, column)
+    );
+}
+
 pub fn runtime_error(error: RuntimeError) {
-    eprintln!("{}\n[line {}]", error.message, error.token.line);
+    eprintln!(
+        "{}\n[{}]{}",
+        error.kind,
+        location(error.token.line, error.token.column),
+        snippet(error.token.line, error.token.column)
+    );
     unsafe { HAD_RUNTIME_ERROR = true };
 }
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    if args.len() < 3 {
+    let (flags, positional): (Vec<&String>, Vec<&String>) =
+        args[1..].iter().partition(|arg| arg.starts_with("--"));
+    if positional.len() < 2 {
         writeln!(io::stderr(), "Usage: {} tokenize <filename>", args[0]).unwrap();
         return;
     }
 
-    let command = &args[1];
-    let filename = &args[2];
+    let command = positional[0];
+    let filename = positional[1];
+    let time = flags.iter().any(|flag| flag.as_str() == "--time");
+    let no_prelude = flags.iter().any(|flag| flag.as_str() == "--no-prelude");
+    let json = flags.iter().any(|flag| flag.as_str() == "--json");
+    let allow_fs = flags.iter().any(|flag| flag.as_str() == "--allow-fs");
+    unsafe { EVAL = flags.iter().any(|flag| flag.as_str() == "--eval") };
+    let mut max_operations = None;
+    for flag in &flags {
+        if let Some(max_errors) = flag.strip_prefix("--max-errors=") {
+            match max_errors.parse::<usize>() {
+                // at least one, so there's always an error to show before aborting
+                Ok(max_errors) if max_errors > 0 => unsafe { MAX_ERRORS = max_errors },
+                _ => {
+                    writeln!(io::stderr(), "Invalid --max-errors value: {}", max_errors).unwrap();
+                    return;
+                }
+            }
+        }
+        if let Some(tab_width) = flag.strip_prefix("--tab-width=") {
+            match tab_width.parse::<usize>() {
+                Ok(tab_width) => unsafe { TAB_WIDTH = tab_width },
+                Err(_) => {
+                    writeln!(io::stderr(), "Invalid --tab-width value: {}", tab_width).unwrap();
+                    return;
+                }
+            }
+        }
+        if let Some(limit) = flag.strip_prefix("--max-operations=") {
+            match limit.parse::<usize>() {
+                Ok(limit) => max_operations = Some(limit),
+                Err(_) => {
+                    writeln!(io::stderr(), "Invalid --max-operations value: {}", limit).unwrap();
+                    return;
+                }
+            }
+        }
+    }
+    let new_interpreter = || {
+        // scripts can't touch the filesystem unless asked for
+        let mut capabilities = Capabilities::new();
+        capabilities.filesystem = allow_fs;
+        return match max_operations {
+            Some(limit) => Interpreter::with_limit(capabilities, limit),
+            None => Interpreter::new(capabilities),
+        };
+    };
 
     match command.as_str() {
         "tokenize" => {
             let tokens = tokenize(filename);
-            for token in tokens {
-                println!("{}", token.to_string());
+            if json {
+                let objects: Vec<String> = tokens.iter().map(|token| token.to_json()).collect();
+                println!("[\n  {}\n]", objects.join(",\n  "));
+            } else {
+                for token in tokens {
+                    println!("{}", token.to_string());
+                }
             }
 
             if unsafe { HAD_ERROR } {
@@ -78,6 +226,18 @@ fn main() {
 
             println!("{}", AstPrinter::new().print(&expr.unwrap()));
         }
+        // like parse, but for whole programs: one s-expression per top level statement
+        "parse-stmts" => {
+            let statement_options = parse(filename);
+
+            if unsafe { HAD_ERROR } {
+                std::process::exit(65);
+            }
+
+            for statement in statement_options.into_iter().flatten() {
+                println!("{}", statement);
+            }
+        }
         "evaluate" => {
             let expr = parse_expr(filename);
 
@@ -85,24 +245,51 @@ fn main() {
                 std::process::exit(65);
             }
 
-            Interpreter::new().interpret_expr(expr.unwrap());
+            new_interpreter().interpret_expr(expr.unwrap());
 
             if unsafe { HAD_RUNTIME_ERROR } {
                 std::process::exit(70);
             }
         }
-        "run" => {
+        "format" => {
             let statement_options = parse(filename);
 
             if unsafe { HAD_ERROR } {
                 std::process::exit(65);
             }
 
+            let statements: Vec<Stmt> = statement_options.into_iter().flatten().collect();
+
+            print!("{}", SourceFormatter::new().format(&statements));
+        }
+        "run" => {
+            let mut timings = Vec::new();
+
+            let started = Instant::now();
+            let tokens = tokenize(filename);
+            timings.push(("scan", started.elapsed()));
+
+            let started = Instant::now();
+            let statement_options = Parser::new(tokens).parse();
+            timings.push(("parse", started.elapsed()));
+
+            if unsafe { HAD_ERROR } {
+                report_timings(time, &timings);
+                std::process::exit(65);
+            }
+
             // would have had errors, and exited, if any of the options were None
             let statements: Vec<Stmt> = statement_options.into_iter().flatten().collect();
 
-            Interpreter::new().interpret(statements);
+            let started = Instant::now();
+            let mut interpreter = new_interpreter();
+            if !no_prelude {
+                prelude::load(&mut interpreter);
+            }
+            interpreter.interpret(statements);
+            timings.push(("interpret", started.elapsed()));
 
+            report_timings(time, &timings);
             if unsafe { HAD_RUNTIME_ERROR } {
                 std::process::exit(70);
             }
@@ -114,17 +301,38 @@ fn main() {
     }
 }
 
+// goes to stderr so the program's own output is untouched
+fn report_timings(enabled: bool, timings: &Vec<(&str, Duration)>) {
+    if !enabled {
+        return;
+    }
+
+    let mut total = Duration::ZERO;
+    for (phase, duration) in timings {
+        eprintln!("{:<10} {:>12.3}ms", phase, duration.as_secs_f64() * 1000.0);
+        total += *duration;
+    }
+    eprintln!("{:<10} {:>12.3}ms", "total", total.as_secs_f64() * 1000.0);
+}
+
 fn read_file(filename: &String) -> String {
-    return fs::read_to_string(filename).unwrap_or_else(|_| {
+    if unsafe { EVAL } {
+        let _ = SOURCE.set(filename.clone());
+        return filename.clone();
+    }
+
+    let source = fs::read_to_string(filename).unwrap_or_else(|_| {
         writeln!(io::stderr(), "Failed to read file {}", filename).unwrap();
         return String::new();
     });
+    let _ = SOURCE.set(source.clone());
+    return source;
 }
 
 fn tokenize(filename: &String) -> Vec<Token> {
     let file_contents = read_file(filename);
 
-    let mut scanner = Scanner::new(file_contents);
+    let mut scanner = Scanner::with_tab_width(file_contents, unsafe { TAB_WIDTH });
     return scanner.scan_tokens().clone();
 }
 
@@ -0,0 +1,1122 @@
+use std::{
+    cell::RefCell,
+    cmp::Ordering,
+    fs,
+    io::{self, Write},
+    ops::RangeInclusive,
+    rc::Rc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    environment::Environment,
+    interpreter::{index_cast, Interpreter, RuntimeError, RuntimeErrorKind, RuntimeExceptions},
+    lox_callables::{LoxAnonymous, LoxCallables, NativeCall},
+    token::{LiteralValue, Token},
+};
+
+type NativeResult = Result<Option<LiteralValue>, RuntimeExceptions>;
+
+// which groups of natives a host lets scripts use. everything else is always defined
+#[derive(Clone, Copy, Debug)]
+pub struct Capabilities {
+    // read_file and write_file
+    pub filesystem: bool,
+    // clock and clockMillis
+    pub clock: bool,
+}
+
+impl Capabilities {
+    // what the cli allows unless told otherwise
+    pub fn new() -> Capabilities {
+        return Capabilities {
+            filesystem: false,
+            clock: true,
+        };
+    }
+
+    // for embedding untrusted scripts
+    #[cfg(test)]
+    pub fn sandboxed() -> Capabilities {
+        return Capabilities {
+            filesystem: false,
+            clock: false,
+        };
+    }
+}
+
+pub fn define(globals: &Environment, capabilities: Capabilities) {
+    if capabilities.clock {
+        define_native(globals, "clock", clock, 0..=0);
+        define_native(globals, "clockMillis", clock_millis, 0..=0);
+    }
+    if capabilities.filesystem {
+        define_native(globals, "read_file", read_file, 1..=1);
+        define_native(globals, "write_file", write_file, 2..=2);
+    }
+    define_native(globals, "len", len, 1..=1);
+    define_native(globals, "push", push, 2..=2);
+    define_native(globals, "pop", pop, 1..=1);
+    define_native(globals, "get", get, 2..=2);
+    define_native(globals, "clone", clone, 1..=1);
+    define_native(globals, "min", min, 1..=1);
+    define_native(globals, "max", max, 1..=1);
+    define_native(globals, "sum", sum, 1..=1);
+    define_native(globals, "typeof", type_of, 1..=1);
+    define_native(globals, "num", num, 1..=1);
+    define_native(globals, "str", str, 1..=1);
+    define_native(globals, "write", write, 1..=1);
+    define_native(globals, "eprint", eprint, 1..=1);
+    define_native(globals, "json", json, 1..=1);
+    define_native(globals, "assert_eq", assert_eq, 2..=2);
+    define_native(globals, "replace", replace, 3..=3);
+    define_native(globals, "split", split, 2..=2);
+    define_native(globals, "join", join, 2..=2);
+    define_native(globals, "random", random, 0..=0);
+    define_native(globals, "seed", seed, 1..=1);
+    define_native(globals, "format", format, 2..=2);
+    define_native(globals, "sort", sort, 1..=2);
+    define_native(globals, "contains", contains, 2..=2);
+    define_native(globals, "indexOf", index_of, 2..=2);
+}
+
+fn define_native(
+    globals: &Environment,
+    name: &'static str,
+    call: NativeCall,
+    arity: RangeInclusive<usize>,
+) {
+    globals.define(
+        name.to_owned(),
+        Some(LiteralValue::LoxCallable(LoxCallables::LoxAnonymous(
+            Box::new(LoxAnonymous::new(name, call, arity)),
+        ))),
+    );
+}
+
+fn error(paren: &Token, kind: RuntimeErrorKind) -> RuntimeExceptions {
+    return RuntimeExceptions::RuntimeError(RuntimeError::new(paren, kind));
+}
+
+fn list_cast(
+    paren: &Token,
+    value: &Option<LiteralValue>,
+) -> Result<Rc<RefCell<Vec<Option<LiteralValue>>>>, RuntimeExceptions> {
+    return match value {
+        Some(LiteralValue::List(values)) => Ok(Rc::clone(values)),
+        _ => Err(error(
+            paren,
+            RuntimeErrorKind::TypeMismatch("Argument must be a list."),
+        )),
+    };
+}
+
+fn string_cast(paren: &Token, value: &Option<LiteralValue>) -> Result<String, RuntimeExceptions> {
+    return match value {
+        Some(LiteralValue::String(value)) => Ok(value.clone()),
+        _ => Err(error(
+            paren,
+            RuntimeErrorKind::TypeMismatch("Argument must be a string."),
+        )),
+    };
+}
+
+fn clock(
+    _interpreter: &mut Interpreter,
+    _paren: &Token,
+    _arguments: Vec<Option<LiteralValue>>,
+) -> NativeResult {
+    return Ok(Some(LiteralValue::Number(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs_f64(),
+    )));
+}
+
+fn clock_millis(
+    interpreter: &mut Interpreter,
+    _paren: &Token,
+    _arguments: Vec<Option<LiteralValue>>,
+) -> NativeResult {
+    return Ok(Some(LiteralValue::Number(
+        interpreter.start_time.elapsed().as_secs_f64() * 1000.0,
+    )));
+}
+
+fn len(
+    _interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Option<LiteralValue>>,
+) -> NativeResult {
+    return match arguments.first().unwrap() {
+        // count characters rather than bytes so multi-byte text has the expected length
+        Some(LiteralValue::String(value)) => {
+            Ok(Some(LiteralValue::Integer(value.chars().count() as i64)))
+        }
+        Some(LiteralValue::List(values)) => {
+            Ok(Some(LiteralValue::Integer(values.borrow().len() as i64)))
+        }
+        _ => Err(error(
+            paren,
+            RuntimeErrorKind::TypeMismatch("Can only get the length of strings and lists."),
+        )),
+    };
+}
+
+fn push(
+    _interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Option<LiteralValue>>,
+) -> NativeResult {
+    let list = list_cast(paren, arguments.first().unwrap())?;
+    list.borrow_mut().push(arguments.get(1).unwrap().clone());
+    return Ok(None);
+}
+
+fn pop(
+    _interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Option<LiteralValue>>,
+) -> NativeResult {
+    let list = list_cast(paren, arguments.first().unwrap())?;
+    let value = list.borrow_mut().pop();
+    return value.ok_or_else(|| {
+        error(
+            paren,
+            RuntimeErrorKind::InvalidValue("Can't pop from an empty list.".to_string()),
+        )
+    });
+}
+
+fn get(
+    _interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Option<LiteralValue>>,
+) -> NativeResult {
+    let list = list_cast(paren, arguments.first().unwrap())?;
+    let values = list.borrow();
+    let index = index_cast(paren, arguments.get(1).unwrap(), values.len(), "List")?;
+    return Ok(values.get(index).unwrap().clone());
+}
+
+// a new list holding the same elements, so the copy can be mutated on its own.
+// every other value is immutable and is returned as is
+fn clone(
+    _interpreter: &mut Interpreter,
+    _paren: &Token,
+    arguments: Vec<Option<LiteralValue>>,
+) -> NativeResult {
+    return match arguments.first().unwrap() {
+        Some(LiteralValue::List(values)) => Ok(Some(LiteralValue::List(Rc::new(RefCell::new(
+            values.borrow().clone(),
+        ))))),
+        value => Ok(value.clone()),
+    };
+}
+
+fn numbers_cast(
+    paren: &Token,
+    value: &Option<LiteralValue>,
+) -> Result<Vec<Option<LiteralValue>>, RuntimeExceptions> {
+    let values = list_cast(paren, value)?.borrow().clone();
+    if values.iter().any(|value| {
+        !matches!(
+            value,
+            Some(LiteralValue::Integer(_)) | Some(LiteralValue::Number(_))
+        )
+    }) {
+        return Err(error(
+            paren,
+            RuntimeErrorKind::TypeMismatch("List elements must be numbers."),
+        ));
+    }
+    return Ok(values);
+}
+
+// integers compare exactly, anything involving a float compares as floats
+fn compare_numbers(a: &Option<LiteralValue>, b: &Option<LiteralValue>) -> Option<Ordering> {
+    if let (Some(LiteralValue::Integer(a)), Some(LiteralValue::Integer(b))) = (a, b) {
+        return Some(a.cmp(b));
+    }
+    let a = f64::try_from(a.clone().unwrap()).unwrap();
+    let b = f64::try_from(b.clone().unwrap()).unwrap();
+    return a.partial_cmp(&b);
+}
+
+// the first element ordered `wanted` against all others; NaN never compares, so never wins
+fn extremum(
+    paren: &Token,
+    arguments: Vec<Option<LiteralValue>>,
+    name: &str,
+    wanted: Ordering,
+) -> NativeResult {
+    let values = numbers_cast(paren, arguments.first().unwrap())?;
+    let mut best: Option<&Option<LiteralValue>> = None;
+    for value in values.iter() {
+        if best.is_none() || compare_numbers(value, best.unwrap()) == Some(wanted) {
+            best = Some(value);
+        }
+    }
+    return best.cloned().ok_or_else(|| {
+        error(
+            paren,
+            RuntimeErrorKind::InvalidValue(format!("Can't get the {} of an empty list.", name)),
+        )
+    });
+}
+
+fn min(
+    _interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Option<LiteralValue>>,
+) -> NativeResult {
+    return extremum(paren, arguments, "min", Ordering::Less);
+}
+
+fn max(
+    _interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Option<LiteralValue>>,
+) -> NativeResult {
+    return extremum(paren, arguments, "max", Ordering::Greater);
+}
+
+// adds like '+' does, so a list of integers sums exactly and an empty list sums to 0
+fn sum(
+    interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Option<LiteralValue>>,
+) -> NativeResult {
+    let values = numbers_cast(paren, arguments.first().unwrap())?;
+    let mut total = Some(LiteralValue::Integer(0));
+    for value in values.iter() {
+        total = interpreter.arithmetic(paren, &total, value, i64::checked_add, |a, b| a + b)?;
+    }
+    return Ok(total);
+}
+
+fn type_of(
+    _interpreter: &mut Interpreter,
+    _paren: &Token,
+    arguments: Vec<Option<LiteralValue>>,
+) -> NativeResult {
+    let name = match arguments.first().unwrap() {
+        None => "nil",
+        Some(LiteralValue::Number(_)) | Some(LiteralValue::Integer(_)) => "number",
+        Some(LiteralValue::String(_)) => "string",
+        Some(LiteralValue::Boolean(_)) => "boolean",
+        Some(LiteralValue::LoxCallable(_)) => "function",
+        Some(LiteralValue::List(_)) => "list",
+    };
+    return Ok(Some(LiteralValue::String(name.to_string())));
+}
+
+fn num(
+    _interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Option<LiteralValue>>,
+) -> NativeResult {
+    let value = string_cast(paren, arguments.first().unwrap())?;
+    let value = value.trim();
+
+    // rust also parses things like "inf" and "1e3", which aren't lox numbers
+    let is_numeric = value
+        .strip_prefix('-')
+        .unwrap_or(value)
+        .chars()
+        .all(|c| c.is_ascii_digit() || c == '.');
+    if !is_numeric {
+        return Err(error(
+            paren,
+            RuntimeErrorKind::InvalidValue(format!("Can't convert '{}' to a number.", value)),
+        ));
+    }
+    // same rule as number literals: no '.' means an integer, if it fits
+    if let Ok(integer) = value.parse::<i64>() {
+        return Ok(Some(LiteralValue::Integer(integer)));
+    }
+    return match value.parse::<f64>() {
+        Ok(number) => Ok(Some(LiteralValue::Number(number))),
+        _ => Err(error(
+            paren,
+            RuntimeErrorKind::InvalidValue(format!("Can't convert '{}' to a number.", value)),
+        )),
+    };
+}
+
+fn str(
+    interpreter: &mut Interpreter,
+    _paren: &Token,
+    arguments: Vec<Option<LiteralValue>>,
+) -> NativeResult {
+    return Ok(Some(LiteralValue::String(
+        interpreter.stringify(arguments.first().unwrap()),
+    )));
+}
+
+// like print, but without the trailing newline
+fn write(
+    interpreter: &mut Interpreter,
+    _paren: &Token,
+    arguments: Vec<Option<LiteralValue>>,
+) -> NativeResult {
+    print!("{}", interpreter.stringify(arguments.first().unwrap()));
+    io::stdout().flush().unwrap();
+    return Ok(None);
+}
+
+// like print, but to the interpreter's error sink so stdout stays clean
+fn eprint(
+    interpreter: &mut Interpreter,
+    _paren: &Token,
+    arguments: Vec<Option<LiteralValue>>,
+) -> NativeResult {
+    let line = interpreter.stringify(arguments.first().unwrap());
+    writeln!(interpreter.err, "{}", line).unwrap();
+    return Ok(None);
+}
+
+// uses the same equality as '==', so assert_eq(1, "1") fails and lists compare by identity
+fn assert_eq(
+    interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Option<LiteralValue>>,
+) -> NativeResult {
+    let actual = arguments.first().unwrap();
+    let expected = arguments.get(1).unwrap();
+    if interpreter.is_equal(actual, expected) {
+        return Ok(None);
+    }
+    return Err(error(
+        paren,
+        RuntimeErrorKind::AssertionFailed(format!(
+            "Expected {} but got {}.",
+            interpreter.stringify(expected),
+            interpreter.stringify(actual)
+        )),
+    ));
+}
+
+fn json(
+    interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Option<LiteralValue>>,
+) -> NativeResult {
+    let json = interpreter.jsonify(paren, arguments.first().unwrap(), &mut Vec::new())?;
+    return Ok(Some(LiteralValue::String(json)));
+}
+
+fn replace(
+    _interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Option<LiteralValue>>,
+) -> NativeResult {
+    let value = string_cast(paren, arguments.first().unwrap())?;
+    let from = string_cast(paren, arguments.get(1).unwrap())?;
+    let to = string_cast(paren, arguments.get(2).unwrap())?;
+    if from.is_empty() {
+        return Err(error(
+            paren,
+            RuntimeErrorKind::InvalidValue("Can't replace an empty string.".to_string()),
+        ));
+    }
+    return Ok(Some(LiteralValue::String(value.replace(&from, &to))));
+}
+
+fn split(
+    _interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Option<LiteralValue>>,
+) -> NativeResult {
+    let value = string_cast(paren, arguments.first().unwrap())?;
+    let separator = string_cast(paren, arguments.get(1).unwrap())?;
+    if separator.is_empty() {
+        return Err(error(
+            paren,
+            RuntimeErrorKind::InvalidValue("Separator can't be empty.".to_string()),
+        ));
+    }
+    let parts = value
+        .split(&separator)
+        .map(|part| Some(LiteralValue::String(part.to_string())))
+        .collect();
+    return Ok(Some(LiteralValue::List(Rc::new(RefCell::new(parts)))));
+}
+
+// splitmix64, so nearby seeds give unrelated sequences and the state is never zero
+pub fn scramble_seed(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^= z >> 31;
+    return if z == 0 { 1 } else { z };
+}
+
+// builds the whole string in one buffer, instead of the copy per step a '+' loop makes
+fn join(
+    interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Option<LiteralValue>>,
+) -> NativeResult {
+    let list = list_cast(paren, arguments.first().unwrap())?;
+    let separator = string_cast(paren, arguments.get(1).unwrap())?;
+    let mut joined = String::new();
+    for (index, value) in list.borrow().iter().enumerate() {
+        if index > 0 {
+            joined.push_str(&separator);
+        }
+        joined.push_str(&interpreter.stringify(value));
+    }
+    return Ok(Some(LiteralValue::String(joined)));
+}
+
+// xorshift64*, returning a float in [0, 1)
+fn random(
+    interpreter: &mut Interpreter,
+    _paren: &Token,
+    _arguments: Vec<Option<LiteralValue>>,
+) -> NativeResult {
+    let mut x = interpreter.random_state;
+    x ^= x >> 12;
+    x ^= x << 25;
+    x ^= x >> 27;
+    interpreter.random_state = x;
+    let bits = x.wrapping_mul(0x2545f4914f6cdd1d) >> 11;
+    return Ok(Some(LiteralValue::Number(
+        bits as f64 / (1u64 << 53) as f64,
+    )));
+}
+
+fn seed(
+    interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Option<LiteralValue>>,
+) -> NativeResult {
+    let seed = match arguments.first().unwrap() {
+        Some(LiteralValue::Integer(value)) => *value as u64,
+        // a whole float seeds like the integer it equals, so seed(42.0) is seed(42)
+        Some(LiteralValue::Number(value))
+            if value.fract() == 0.0 && *value >= i64::MIN as f64 && *value < i64::MAX as f64 =>
+        {
+            *value as i64 as u64
+        }
+        Some(LiteralValue::Number(value)) => value.to_bits(),
+        _ => {
+            return Err(error(
+                paren,
+                RuntimeErrorKind::TypeMismatch("Argument must be a number."),
+            ))
+        }
+    };
+    interpreter.random_state = scramble_seed(seed);
+    return Ok(None);
+}
+
+const MAX_DECIMALS: usize = 100;
+
+// a number as a string with a fixed count of decimals, e.g. format(3.14159, 2) is "3.14"
+fn format(
+    _interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Option<LiteralValue>>,
+) -> NativeResult {
+    let value = match arguments.first().unwrap() {
+        Some(LiteralValue::Integer(value)) => *value as f64,
+        Some(LiteralValue::Number(value)) => *value,
+        _ => {
+            return Err(error(
+                paren,
+                RuntimeErrorKind::TypeMismatch("First argument must be a number."),
+            ))
+        }
+    };
+    let decimals = match arguments.get(1).unwrap() {
+        Some(LiteralValue::Integer(decimals)) if *decimals >= 0 => *decimals as f64,
+        Some(LiteralValue::Number(decimals)) if *decimals >= 0.0 && decimals.fract() == 0.0 => {
+            *decimals
+        }
+        _ => {
+            return Err(error(
+                paren,
+                RuntimeErrorKind::InvalidValue(
+                    "Decimals must be a non-negative integer.".to_string(),
+                ),
+            ))
+        }
+    };
+    // std's formatter panics on huge precisions, and nothing past this is meaningful anyway
+    if decimals > MAX_DECIMALS as f64 {
+        return Err(error(
+            paren,
+            RuntimeErrorKind::InvalidValue(format!("Decimals can be at most {}.", MAX_DECIMALS)),
+        ));
+    }
+    let decimals = decimals as usize;
+    let mut string = format!("{:.*}", decimals, value);
+    // a small negative that rounds to zero shouldn't keep its sign
+    if string.starts_with('-') && string[1..].chars().all(|char| char == '0' || char == '.') {
+        string.remove(0);
+    }
+    return Ok(Some(LiteralValue::String(string)));
+}
+
+// a stable merge sort whose comparison can fail, so a comparator that errors stops the
+// sort, and one that isn't a consistent order just gives an odd result instead of a panic
+fn merge_sort<F>(
+    mut values: Vec<Option<LiteralValue>>,
+    in_order: &mut F,
+) -> Result<Vec<Option<LiteralValue>>, RuntimeExceptions>
+where
+    F: FnMut(&Option<LiteralValue>, &Option<LiteralValue>) -> Result<bool, RuntimeExceptions>,
+{
+    if values.len() <= 1 {
+        return Ok(values);
+    }
+    let right = values.split_off(values.len() / 2);
+    let mut left = merge_sort(values, in_order)?.into_iter().peekable();
+    let mut right = merge_sort(right, in_order)?.into_iter().peekable();
+
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    while let (Some(a), Some(b)) = (left.peek(), right.peek()) {
+        if in_order(a, b)? {
+            merged.push(left.next().unwrap());
+        } else {
+            merged.push(right.next().unwrap());
+        }
+    }
+    merged.extend(left);
+    merged.extend(right);
+    return Ok(merged);
+}
+
+// sorts a list in place, numbers or strings ascending by default. a cmp(a, b) given as the
+// second argument orders it instead: negative when a goes first, positive when b does, and zero
+// to keep their order
+fn sort(
+    interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Option<LiteralValue>>,
+) -> NativeResult {
+    let list = list_cast(paren, arguments.first().unwrap())?;
+    let comparator = match arguments.get(1) {
+        None => None,
+        Some(Some(LiteralValue::LoxCallable(callable))) => Some(callable.clone()),
+        Some(_) => {
+            return Err(error(
+                paren,
+                RuntimeErrorKind::TypeMismatch("Comparator must be a function."),
+            ))
+        }
+    };
+
+    // sorts a copy, so a comparator is free to look at or change the list meanwhile
+    let values = list.borrow().clone();
+    let sorted = match comparator {
+        Some(comparator) => merge_sort(values, &mut |a, b| {
+            let order =
+                interpreter.call_function(comparator.clone(), paren, vec![a.clone(), b.clone()])?;
+            return match order {
+                Some(LiteralValue::Integer(order)) => Ok(order <= 0),
+                Some(LiteralValue::Number(order)) => Ok(order <= 0.0),
+                _ => Err(error(
+                    paren,
+                    RuntimeErrorKind::TypeMismatch("Comparator must return a number."),
+                )),
+            };
+        })?,
+        None => {
+            let numbers = values.iter().all(|value| {
+                matches!(
+                    value,
+                    Some(LiteralValue::Integer(_)) | Some(LiteralValue::Number(_))
+                )
+            });
+            let strings = values
+                .iter()
+                .all(|value| matches!(value, Some(LiteralValue::String(_))));
+            if !numbers && !strings {
+                return Err(error(
+                    paren,
+                    RuntimeErrorKind::TypeMismatch(
+                        "List elements must be all numbers or all strings.",
+                    ),
+                ));
+            }
+            merge_sort(values, &mut |a, b| {
+                if let (Some(LiteralValue::String(a)), Some(LiteralValue::String(b))) = (a, b) {
+                    return Ok(a <= b);
+                }
+                return Ok(compare_numbers(a, b) != Some(Ordering::Greater));
+            })?
+        }
+    };
+    *list.borrow_mut() = sorted;
+    return Ok(arguments.into_iter().next().unwrap());
+}
+
+fn read_file(
+    _interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Option<LiteralValue>>,
+) -> NativeResult {
+    let path = string_cast(paren, arguments.first().unwrap())?;
+    return match fs::read_to_string(&path) {
+        Ok(contents) => Ok(Some(LiteralValue::String(contents))),
+        Err(err) => Err(error(
+            paren,
+            RuntimeErrorKind::Io(format!("Can't read file '{}': {}.", path, err)),
+        )),
+    };
+}
+
+// replaces the file's contents, creating it if needed
+fn write_file(
+    _interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Option<LiteralValue>>,
+) -> NativeResult {
+    let path = string_cast(paren, arguments.first().unwrap())?;
+    let contents = string_cast(paren, arguments.get(1).unwrap())?;
+    return match fs::write(&path, contents) {
+        Ok(()) => Ok(None),
+        Err(err) => Err(error(
+            paren,
+            RuntimeErrorKind::Io(format!("Can't write file '{}': {}.", path, err)),
+        )),
+    };
+}
+
+// where needle first appears in a string (as a substring, counted in characters) or a list
+// (as an element), or None if it doesn't
+fn find(
+    interpreter: &Interpreter,
+    paren: &Token,
+    arguments: &[Option<LiteralValue>],
+) -> Result<Option<usize>, RuntimeExceptions> {
+    let needle = arguments.get(1).unwrap();
+    return match arguments.first().unwrap() {
+        Some(LiteralValue::String(haystack)) => {
+            let needle = match needle {
+                Some(LiteralValue::String(needle)) => needle,
+                _ => {
+                    return Err(error(
+                        paren,
+                        RuntimeErrorKind::TypeMismatch("Can only search a string for a string."),
+                    ))
+                }
+            };
+            Ok(haystack
+                .find(needle.as_str())
+                .map(|byte| haystack[..byte].chars().count()))
+        }
+        Some(LiteralValue::List(values)) => Ok(values
+            .borrow()
+            .iter()
+            .position(|value| interpreter.is_equal(value, needle))),
+        _ => Err(error(
+            paren,
+            RuntimeErrorKind::TypeMismatch("Can only search strings and lists."),
+        )),
+    };
+}
+
+fn contains(
+    interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Option<LiteralValue>>,
+) -> NativeResult {
+    let found = find(interpreter, paren, &arguments)?;
+    return Ok(Some(LiteralValue::Boolean(found.is_some())));
+}
+
+// -1 when the needle isn't there
+fn index_of(
+    interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Option<LiteralValue>>,
+) -> NativeResult {
+    let found = find(interpreter, paren, &arguments)?;
+    return Ok(Some(LiteralValue::Integer(match found {
+        Some(index) => index as i64,
+        None => -1,
+    })));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, io::Write, rc::Rc};
+
+    use super::Capabilities;
+    use crate::{
+        interpreter::{Interpreter, RuntimeError},
+        parser::parse_program,
+        test_support::{global, result, run_error},
+        token::LiteralValue,
+    };
+
+    // a sink that can still be read after the interpreter takes ownership of it
+    #[derive(Clone)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            return self.0.borrow_mut().write(buf);
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            return Ok(());
+        }
+    }
+
+    #[test]
+    fn format_rounds_to_the_given_decimals() {
+        assert_eq!(result("var result = format(3.14159, 2);"), "3.14");
+        assert_eq!(result("var result = format(1.96, 1);"), "2.0");
+        assert_eq!(result("var result = format(2.5, 0);"), "2");
+        assert_eq!(result("var result = format(7, 3);"), "7.000");
+    }
+
+    #[test]
+    fn format_drops_the_sign_of_a_negative_zero() {
+        assert_eq!(result("var result = format(-0.001, 2);"), "0.00");
+        assert_eq!(result("var result = format(-1.25, 1);"), "-1.2");
+    }
+
+    #[test]
+    fn format_rejects_bad_decimals() {
+        let message = "Decimals must be a non-negative integer.";
+        assert_eq!(run_error("format(1, -1);"), message);
+        assert_eq!(run_error("format(1, 1.5);"), message);
+        assert_eq!(run_error("format(1, \"2\");"), message);
+        assert_eq!(
+            run_error("format(1, 100000000);"),
+            "Decimals can be at most 100."
+        );
+        assert_eq!(
+            run_error("format(\"1\", 2);"),
+            "First argument must be a number."
+        );
+    }
+
+    #[test]
+    fn clock_millis_never_goes_backwards() {
+        assert_eq!(
+            result("var a = clockMillis(); var b = clockMillis(); var result = a <= b;"),
+            "true"
+        );
+    }
+
+    #[test]
+    fn len_counts_strings_and_lists() {
+        assert_eq!(result("var result = len(\"abc\");"), "3");
+        assert_eq!(result("var result = len(\"☕🎉\");"), "2");
+        assert_eq!(result("var result = len([1, 2]);"), "2");
+        assert_eq!(
+            run_error("len(1);"),
+            "Can only get the length of strings and lists."
+        );
+    }
+
+    #[test]
+    fn list_natives_mutate_the_shared_list() {
+        let source = "
+            var a = [1];
+            var b = a;
+            push(b, 2);
+            var popped = pop(a);
+            var result = [a, b, popped, get(b, 0)];
+        ";
+        assert_eq!(result(source), "[[1], [1], 2, 1]");
+    }
+
+    #[test]
+    fn list_natives_reject_bad_arguments() {
+        assert_eq!(run_error("pop([]);"), "Can't pop from an empty list.");
+        assert_eq!(run_error("get([1], 5);"), "List index out of range.");
+        assert_eq!(run_error("push(1, 2);"), "Argument must be a list.");
+    }
+
+    #[test]
+    fn typeof_names_each_kind_of_value() {
+        let source = "
+            fun f() {}
+            var result = [typeof(nil), typeof(1), typeof(1.5), typeof(\"s\"), typeof(true), typeof([]), typeof(f), typeof(clock)];
+        ";
+        assert_eq!(
+            result(source),
+            "[nil, number, number, string, boolean, list, function, function]"
+        );
+    }
+
+    #[test]
+    fn num_and_str_convert_between_strings_and_numbers() {
+        assert_eq!(result("var result = num(\"3.5\");"), "3.5");
+        assert_eq!(result("var result = num(\" 4 \");"), "4");
+        assert_eq!(result("var result = str(42) + \"!\";"), "42!");
+        assert_eq!(
+            run_error("num(\"abc\");"),
+            "Can't convert 'abc' to a number."
+        );
+        assert_eq!(run_error("num(\"\");"), "Can't convert '' to a number.");
+    }
+
+    #[test]
+    fn json_serializes_nested_lists() {
+        assert_eq!(
+            result("var result = json([1, \"a\\x22b\", [true, nil, []], 1.5]);"),
+            "[1,\"a\\\"b\",[true,null,[]],1.5]"
+        );
+    }
+
+    #[test]
+    fn json_rejects_functions_and_cycles() {
+        assert_eq!(
+            run_error("json(clock);"),
+            "Can't convert '<anonymous function>' to json."
+        );
+        assert_eq!(
+            run_error("var l = [1]; push(l, l); json(l);"),
+            "Can't convert a list that contains itself to json."
+        );
+    }
+
+    #[test]
+    fn replace_handles_no_match_and_many_matches() {
+        assert_eq!(
+            result("var result = replace(\"a-b-c\", \"-\", \"+\");"),
+            "a+b+c"
+        );
+        assert_eq!(
+            result("var result = replace(\"abc\", \"x\", \"y\");"),
+            "abc"
+        );
+        assert_eq!(
+            run_error("replace(\"a\", \"\", \"x\");"),
+            "Can't replace an empty string."
+        );
+    }
+
+    #[test]
+    fn split_handles_no_match_and_many_matches() {
+        assert_eq!(
+            result("var result = split(\"a,b,,c\", \",\");"),
+            "[a, b, , c]"
+        );
+        assert_eq!(result("var result = split(\"abc\", \";\");"), "[abc]");
+        assert_eq!(
+            run_error("split(\"a\", \"\");"),
+            "Separator can't be empty."
+        );
+    }
+
+    #[test]
+    fn seeding_gives_the_same_sequence_in_every_interpreter() {
+        let source = "seed(42); var result = [random(), random(), random()];";
+        assert_eq!(result(source), result(source));
+        assert_ne!(
+            result(source),
+            result("seed(43); var result = [random(), random(), random()];")
+        );
+    }
+
+    #[test]
+    fn whole_float_seeds_match_their_integer() {
+        let sequence = |seed: &str| {
+            return result(&format!(
+                "seed({}); var result = [random(), random(), random()];",
+                seed
+            ));
+        };
+        assert_eq!(sequence("42"), sequence("42.0"));
+        assert_eq!(sequence("-7"), sequence("-7.0"));
+        assert_ne!(sequence("42"), sequence("42.5"));
+    }
+
+    #[test]
+    fn eprint_writes_lines_to_the_error_sink() {
+        let buffer = SharedBuffer(Rc::new(RefCell::new(Vec::new())));
+        let mut interpreter = Interpreter::new(Capabilities::new());
+        interpreter.err = Box::new(buffer.clone());
+        let statements = parse_program("eprint(\"oops\"); eprint(1);").unwrap();
+        interpreter.try_interpret(statements).unwrap();
+        assert_eq!(String::from_utf8_lossy(&buffer.0.borrow()), "oops\n1\n");
+    }
+
+    #[test]
+    fn mutating_a_clone_leaves_the_original_alone() {
+        let source = "
+            var original = [1, [2]];
+            var copy = clone(original);
+            push(copy, 3);
+            var result = [original, copy, copy[1] == original[1]];
+        ";
+        // the copy is shallow, so nested lists are still shared
+        assert_eq!(result(source), "[[1, [2]], [1, [2], 3], true]");
+    }
+
+    #[test]
+    fn min_max_and_sum_fold_numeric_lists() {
+        assert_eq!(
+            result("var result = [min([3, 1.5, 2]), max([3, 1.5, 2]), sum([1, 2, 3]), sum([])];"),
+            "[1.5, 3, 6, 0]"
+        );
+    }
+
+    #[test]
+    fn min_max_and_sum_reject_bad_lists() {
+        assert_eq!(run_error("min([]);"), "Can't get the min of an empty list.");
+        assert_eq!(
+            run_error("sum([1, \"a\"]);"),
+            "List elements must be numbers."
+        );
+        assert_eq!(run_error("max(1);"), "Argument must be a list.");
+    }
+
+    #[test]
+    fn assert_eq_shows_both_values_when_they_differ() {
+        assert_eq!(result("assert_eq(1 + 1, 2); var result = \"ok\";"), "ok");
+        assert_eq!(
+            run_error("assert_eq([1, 2], \"x\");"),
+            "Expected x but got [1, 2]."
+        );
+    }
+
+    #[test]
+    fn sort_orders_numbers_and_strings() {
+        assert_eq!(
+            result("var result = [sort([3, 1.5, 2]), sort([\"b\", \"a\"])];"),
+            "[[1.5, 2, 3], [a, b]]"
+        );
+        assert_eq!(
+            run_error("sort([1, \"a\"]);"),
+            "List elements must be all numbers or all strings."
+        );
+    }
+
+    #[test]
+    fn sort_uses_a_comparator_when_given_one() {
+        let source = "
+            fun descending(a, b) { return b - a; }
+            var result = sort([3, 1, 2], descending);
+        ";
+        assert_eq!(result(source), "[3, 2, 1]");
+        assert_eq!(
+            run_error("sort([1, 2], 1);"),
+            "Comparator must be a function."
+        );
+        assert_eq!(
+            run_error("sort([1], 1, 2);"),
+            "Expected 1 to 2 arguments but got 3."
+        );
+    }
+
+    fn run_with(capabilities: Capabilities, source: &str) -> Result<Interpreter, RuntimeError> {
+        let mut interpreter = Interpreter::new(capabilities);
+        interpreter.try_interpret(parse_program(source).unwrap())?;
+        return Ok(interpreter);
+    }
+
+    #[test]
+    fn files_round_trip_through_write_file_and_read_file() {
+        let path = std::env::temp_dir().join(format!("lox-natives-{}.txt", std::process::id()));
+        let path = path.to_str().unwrap().replace('\\', "/");
+        let capabilities = Capabilities {
+            filesystem: true,
+            ..Capabilities::new()
+        };
+        let source = format!(
+            "write_file(\"{0}\", \"line\"); var result = read_file(\"{0}\");",
+            path
+        );
+        let interpreter = run_with(capabilities, &source).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            global(&interpreter, "result"),
+            Some(LiteralValue::String("line".to_string()))
+        );
+    }
+
+    #[test]
+    fn reading_a_missing_file_is_an_error() {
+        let capabilities = Capabilities {
+            filesystem: true,
+            ..Capabilities::new()
+        };
+        let error = match run_with(capabilities, "read_file(\"/no/such/file\");") {
+            Ok(_) => panic!("reading a missing file should fail"),
+            Err(error) => error,
+        };
+        assert!(error
+            .kind
+            .to_string()
+            .starts_with("Can't read file '/no/such/file':"));
+    }
+
+    #[test]
+    fn disabled_capabilities_leave_their_natives_undefined() {
+        let message = |capabilities, source| match run_with(capabilities, source) {
+            Ok(_) => panic!("{:?} should fail", source),
+            Err(error) => error.kind.to_string(),
+        };
+        assert_eq!(
+            message(Capabilities::new(), "read_file(\"x\");"),
+            "Undefined variable 'read_file'."
+        );
+        assert_eq!(
+            message(Capabilities::sandboxed(), "clock();"),
+            "Undefined variable 'clock'."
+        );
+        assert_eq!(
+            message(Capabilities::sandboxed(), "clockMillis();"),
+            "Undefined variable 'clockMillis'."
+        );
+        // everything else is still there
+        assert!(run_with(Capabilities::sandboxed(), "len([]);").is_ok());
+    }
+
+    #[test]
+    fn contains_and_index_of_search_strings_and_lists() {
+        assert_eq!(
+            result(
+                "var result = [contains([1, 2], 2), contains(\"abc\", \"bc\"), contains([1], 3)];"
+            ),
+            "[true, true, false]"
+        );
+        assert_eq!(
+            result("var result = [indexOf([1, 2], 2), indexOf(\"abc\", \"c\"), indexOf(\"abc\", \"z\")];"),
+            "[1, 2, -1]"
+        );
+    }
+
+    #[test]
+    fn contains_and_index_of_reject_other_types() {
+        assert_eq!(
+            run_error("contains(1, 1);"),
+            "Can only search strings and lists."
+        );
+        assert_eq!(
+            run_error("indexOf(\"a\", 1);"),
+            "Can only search a string for a string."
+        );
+    }
+
+    #[test]
+    fn join_stringifies_and_separates_elements() {
+        assert_eq!(
+            result("var result = join([1, \"a\", nil], \", \");"),
+            "1, a, nil"
+        );
+        assert_eq!(result("var result = join([], \"-\");"), "");
+    }
+
+    #[test]
+    fn join_builds_large_strings() {
+        let source = "
+            var parts = [];
+            for (var i = 0; i < 100000; i = i + 1) push(parts, \"x\");
+            var result = len(join(parts, \"\"));
+        ";
+        assert_eq!(result(source), "100000");
+    }
+}
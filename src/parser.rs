@@ -1,27 +1,77 @@
+use std::cell::RefCell;
+
 use crate::{
     error_token,
-    expr::{Assign, Binary, Call, Expr, Grouping, Literal, Logical, Unary, Variable},
-    stmt::{Block, Expression, Function, If, Print, Return, Stmt, Var, While},
+    expr::{
+        self, Assign, Binary, Call, Destructure, Expr, Grouping, Index, List, Literal, Logical,
+        NodeId, Pattern, Postfix, Slice, Unary, Variable,
+    },
+    stmt::{
+        self, Assert, Block, Expression, ForEach, Function, If, Print, Repeat, Return, Stmt, Var,
+        While,
+    },
     token::{LiteralValue, Token},
     token_type::TokenType,
 };
 
-pub struct ParseError {}
+#[derive(Clone, Debug)]
+pub struct ParseError {
+    pub token: Token,
+    pub message: String,
+}
 
 impl ParseError {
-    pub fn new() -> ParseError {
-        return ParseError {};
+    pub fn new(token: &Token, message: &str) -> ParseError {
+        return ParseError {
+            token: token.clone(),
+            message: message.to_string(),
+        };
     }
 }
 
+// scans and parses a whole program without reporting anything, for tests
+#[cfg(test)]
+pub fn parse_program(source: &str) -> Result<Vec<Stmt>, Vec<ParseError>> {
+    let tokens = crate::scanner::Scanner::new(source.to_string()).try_scan_tokens()?;
+    return Parser::new(tokens).try_parse();
+}
+
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    // every error so far, including ones reported without unwinding
+    errors: RefCell<Vec<ParseError>>,
+    // whether errors also go to stderr and the global error flag, as the cli wants
+    reporting: bool,
+    // the id the next node gets
+    next_id: NodeId,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Parser {
-        return Parser { tokens, current: 0 };
+        return Parser::with_first_id(tokens, 0);
+    }
+
+    // numbers this parse's nodes from first_id, so its tree can't share ids with another's
+    pub fn with_first_id(tokens: Vec<Token>, first_id: NodeId) -> Parser {
+        return Parser {
+            tokens,
+            current: 0,
+            errors: RefCell::new(Vec::new()),
+            reporting: true,
+            next_id: first_id,
+        };
+    }
+
+    // like parse, but quietly collects the errors and returns them instead of any statements
+    pub fn try_parse(&mut self) -> Result<Vec<Stmt>, Vec<ParseError>> {
+        self.reporting = false;
+        let statements = self.parse();
+        let errors = self.errors.take();
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        return Ok(statements.into_iter().flatten().collect());
     }
 
     pub fn parse(&mut self) -> Vec<Option<Stmt>> {
@@ -38,6 +88,12 @@ impl Parser {
         return statements;
     }
 
+    fn node_id(&mut self) -> NodeId {
+        let id = self.next_id;
+        self.next_id += 1;
+        return id;
+    }
+
     pub fn parse_expr(&mut self) -> Option<Expr> {
         return self.expression().ok();
     }
@@ -49,11 +105,18 @@ impl Parser {
         if self.r#match(&vec![TokenType::VAR]) {
             return self.var_declaration();
         }
+        if self.r#match(&vec![TokenType::CONST]) {
+            return self.const_declaration();
+        }
 
         return self.statement();
     }
 
     fn var_declaration(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.previous().offset;
+        if self.check(&TokenType::LEFT_BRACKET) {
+            return self.destructure_declaration(start);
+        }
         let name = self
             .consume(TokenType::IDENTIFIER, "Expect variable name.")?
             .clone();
@@ -67,19 +130,130 @@ impl Parser {
             TokenType::SEMICOLON,
             "Expect ';' after variable declaration.",
         )?;
-        return Ok(Stmt::Var(Var::new(name, initializer)));
+        return Ok(Stmt::Var(Var::new(
+            name,
+            initializer,
+            false,
+            self.span_from(start),
+            self.node_id(),
+        )));
+    }
+
+    fn destructure_declaration(&mut self, start: usize) -> Result<Stmt, ParseError> {
+        let pattern = self.pattern()?;
+        // there's nothing to take the elements from without an initializer
+        self.consume(TokenType::EQUAL, "Expect '=' after pattern.")?;
+        let initializer = self.expression()?;
+        self.consume(
+            TokenType::SEMICOLON,
+            "Expect ';' after variable declaration.",
+        )?;
+        return Ok(Stmt::Destructure(stmt::Destructure::new(
+            pattern,
+            initializer,
+            self.span_from(start),
+            self.node_id(),
+        )));
+    }
+
+    // `[a, b, ...rest]`, where the rest name is optional and has to come last
+    fn pattern(&mut self) -> Result<Pattern, ParseError> {
+        let bracket = self
+            .consume(TokenType::LEFT_BRACKET, "Expect '[' before pattern.")?
+            .clone();
+        let mut names = Vec::new();
+        let mut rest = None;
+        if !self.check(&TokenType::RIGHT_BRACKET) {
+            loop {
+                if self.r#match(&vec![TokenType::DOT_DOT_DOT]) {
+                    rest = Some(
+                        self.consume(TokenType::IDENTIFIER, "Expect name after '...'.")?
+                            .clone(),
+                    );
+                    break;
+                }
+                names.push(
+                    self.consume(TokenType::IDENTIFIER, "Expect variable name in pattern.")?
+                        .clone(),
+                );
+                if !self.r#match(&vec![TokenType::COMMA]) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RIGHT_BRACKET, "Expect ']' after pattern.")?;
+        return Ok(Pattern::new(bracket, names, rest));
+    }
+
+    // a '[' starts a destructuring assignment only if it holds nothing but names and is
+    // followed by '=', otherwise it's a list literal
+    fn is_pattern_assignment(&self) -> bool {
+        let type_at = |index: usize| self.tokens.get(index).map(|token| &token.r#type);
+        let mut index = self.current + 1;
+        if type_at(index) == Some(&TokenType::RIGHT_BRACKET) {
+            return type_at(index + 1) == Some(&TokenType::EQUAL);
+        }
+        loop {
+            let rest = type_at(index) == Some(&TokenType::DOT_DOT_DOT);
+            if rest {
+                index += 1;
+            }
+            if type_at(index) != Some(&TokenType::IDENTIFIER) {
+                return false;
+            }
+            index += 1;
+            match type_at(index) {
+                Some(TokenType::COMMA) if !rest => index += 1,
+                Some(TokenType::RIGHT_BRACKET) => {
+                    return type_at(index + 1) == Some(&TokenType::EQUAL)
+                }
+                _ => return false,
+            }
+        }
+    }
+
+    fn const_declaration(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.previous().offset;
+        let name = self
+            .consume(TokenType::IDENTIFIER, "Expect constant name.")?
+            .clone();
+
+        // a constant can never be assigned later, so it has to be initialized here
+        self.consume(TokenType::EQUAL, "Expect '=' after constant name.")?;
+        let initializer = self.expression()?;
+
+        self.consume(
+            TokenType::SEMICOLON,
+            "Expect ';' after constant declaration.",
+        )?;
+        return Ok(Stmt::Var(Var::new(
+            name,
+            Some(initializer),
+            true,
+            self.span_from(start),
+            self.node_id(),
+        )));
     }
 
     fn statement(&mut self) -> Result<Stmt, ParseError> {
+        if self.r#match(&vec![TokenType::ASSERT]) {
+            return self.assert_statement();
+        }
         if self.r#match(&vec![TokenType::FOR]) {
             return self.for_statement();
         }
         if self.r#match(&vec![TokenType::IF]) {
             return self.if_statement();
         }
+        if self.r#match(&vec![TokenType::UNLESS]) {
+            return self.unless_statement();
+        }
         if self.r#match(&vec![TokenType::PRINT]) {
             return self.print_statement();
         }
+        if self.r#match(&vec![TokenType::REPEAT]) {
+            return self.repeat_statement();
+        }
         if self.r#match(&vec![TokenType::RETURN]) {
             return self.return_statement();
         }
@@ -87,15 +261,47 @@ impl Parser {
             return self.while_statement();
         }
         if self.r#match(&vec![TokenType::LEFT_BRACE]) {
-            return Ok(Stmt::Block(Block::new(self.block()?)));
+            let start = self.previous().offset;
+            let statements = self.block()?;
+            return Ok(Stmt::Block(Block::new(
+                statements,
+                self.span_from(start),
+                self.node_id(),
+            )));
         }
 
         return self.expression_statement();
     }
 
+    fn assert_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
+        let start = keyword.offset;
+        self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'assert'.")?;
+        let condition = self.expression()?;
+        let mut message: Option<Expr> = None;
+        if self.r#match(&vec![TokenType::COMMA]) {
+            message = Some(self.expression()?);
+        }
+        self.consume(TokenType::RIGHT_PAREN, "Expect ')' after assert arguments.")?;
+        self.consume(TokenType::SEMICOLON, "Expect ';' after assert.")?;
+
+        return Ok(Stmt::Assert(Assert::new(
+            keyword,
+            condition,
+            message,
+            self.span_from(start),
+            self.node_id(),
+        )));
+    }
+
     fn for_statement(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.previous().offset;
         self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'for'.")?;
 
+        if self.check(&TokenType::IDENTIFIER) && self.check_next(&TokenType::IN) {
+            return self.for_each_statement(start);
+        }
+
         let mut initializer: Option<Stmt> = None;
         if self.r#match(&vec![TokenType::SEMICOLON]) {
             // no initializer
@@ -117,44 +323,140 @@ impl Parser {
         }
         self.consume(TokenType::RIGHT_PAREN, "Expect ')' after for clauses.")?;
 
-        let mut body = self.statement()?;
-
-        if increment.is_some() {
-            body = Stmt::Block(Block::new(vec![
-                body,
-                Stmt::Expression(Expression::new(increment.unwrap())),
-            ]));
-        }
+        let body = self.statement()?;
+        // desugared nodes have no source of their own, so they cover the whole loop
+        let span = self.span_from(start);
+
+        // a variable declared in the initializer is rebound on every iteration
+        let loop_variable = match &initializer {
+            Some(Stmt::Var(var)) => Some(var.name.clone()),
+            _ => None,
+        };
         let mut r#while = Stmt::While(While::new(
-            condition.unwrap_or(Expr::Literal(Literal::new(Some(LiteralValue::Boolean(
-                true,
-            ))))),
+            condition.unwrap_or(Expr::Literal(Literal::new(
+                Some(LiteralValue::Boolean(true)),
+                span,
+                self.node_id(),
+            ))),
             body,
+            increment,
+            loop_variable,
+            span,
+            self.node_id(),
         ));
         if initializer.is_some() {
-            r#while = Stmt::Block(Block::new(vec![initializer.unwrap(), r#while]));
+            r#while = Stmt::Block(Block::new(
+                vec![initializer.unwrap(), r#while],
+                span,
+                self.node_id(),
+            ));
         }
 
         return Ok(r#while);
     }
 
+    fn for_each_statement(&mut self, start: usize) -> Result<Stmt, ParseError> {
+        let name = self.advance().clone();
+        let keyword = self.advance().clone();
+        let iterable = self.expression()?;
+        self.consume(TokenType::RIGHT_PAREN, "Expect ')' after for clauses.")?;
+        let body = self.statement()?;
+
+        return Ok(Stmt::ForEach(ForEach::new(
+            name,
+            keyword,
+            iterable,
+            body,
+            self.span_from(start),
+            self.node_id(),
+        )));
+    }
+
+    fn repeat_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
+        let start = keyword.offset;
+        self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'repeat'.")?;
+        let count = self.expression()?;
+        self.consume(TokenType::RIGHT_PAREN, "Expect ')' after repeat count.")?;
+        let body = self.statement()?;
+
+        return Ok(Stmt::Repeat(Repeat::new(
+            keyword,
+            count,
+            body,
+            self.span_from(start),
+            self.node_id(),
+        )));
+    }
+
     fn if_statement(&mut self) -> Result<Stmt, ParseError> {
-        self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'if'.")?;
+        // shared with 'unless', so report whichever keyword started the statement
+        let keyword = self.previous().lexeme.clone();
+        let start = self.previous().offset;
+        self.consume(
+            TokenType::LEFT_PAREN,
+            &format!("Expect '(' after '{keyword}'."),
+        )?;
         let condition = self.expression()?;
-        self.consume(TokenType::RIGHT_PAREN, "Expect ')' after if condition.")?;
+        self.consume(
+            TokenType::RIGHT_PAREN,
+            &format!("Expect ')' after {keyword} condition."),
+        )?;
         let then_branch = self.statement()?;
         let mut else_branch: Option<Stmt> = None;
         if self.r#match(&vec![TokenType::ELSE]) {
             else_branch = Some(self.statement()?);
         }
 
-        return Ok(Stmt::If(If::new(condition, then_branch, else_branch)));
+        return Ok(Stmt::If(If::new(
+            condition,
+            then_branch,
+            else_branch,
+            self.span_from(start),
+            self.node_id(),
+        )));
+    }
+
+    fn unless_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
+        let r#if = self.if_statement()?;
+
+        // desugar into an if with the condition negated
+        return match r#if {
+            Stmt::If(r#if) => {
+                let bang = Token::new(
+                    TokenType::BANG,
+                    "!".to_string(),
+                    None,
+                    keyword.line,
+                    keyword.column,
+                    keyword.offset,
+                );
+                // grouped, so printers show the whole condition negated and not just its left side
+                let condition = *r#if.condition;
+                let span = condition.span();
+                let condition = Expr::Grouping(Grouping::new(condition, span, self.node_id()));
+                Ok(Stmt::If(If::new(
+                    Expr::Unary(Unary::new(bang, condition, self.node_id())),
+                    *r#if.then_branch,
+                    r#if.else_branch.map(|eb| *eb),
+                    r#if.span,
+                    self.node_id(),
+                )))
+            }
+            _ => unreachable!("if_statement only ever returns an if"),
+        };
     }
 
     fn print_statement(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.previous().offset;
         let value = self.expression()?;
         self.consume(TokenType::SEMICOLON, "Expect ';' after value.")?;
-        return Ok(Stmt::Print(Print::new(value)));
+        return Ok(Stmt::Print(Print::new(
+            value,
+            self.span_from(start),
+            self.node_id(),
+        )));
     }
 
     fn return_statement(&mut self) -> Result<Stmt, ParseError> {
@@ -165,16 +467,30 @@ impl Parser {
         }
 
         self.consume(TokenType::SEMICOLON, "Expect ';' after return value.")?;
-        return Ok(Stmt::Return(Return::new(keyword, value)));
+        let span = self.span_from(keyword.offset);
+        return Ok(Stmt::Return(Return::new(
+            keyword,
+            value,
+            span,
+            self.node_id(),
+        )));
     }
 
     fn while_statement(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.previous().offset;
         self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'while'.")?;
         let condition = self.expression()?;
         self.consume(TokenType::RIGHT_PAREN, "Expect ')' after condition.")?;
         let body = self.statement()?;
 
-        return Ok(Stmt::While(While::new(condition, body)));
+        return Ok(Stmt::While(While::new(
+            condition,
+            body,
+            None,
+            None,
+            self.span_from(start),
+            self.node_id(),
+        )));
     }
 
     fn block(&mut self) -> Result<Vec<Stmt>, ParseError> {
@@ -189,12 +505,18 @@ impl Parser {
     }
 
     fn expression_statement(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.peek().offset;
         let expr = self.expression()?;
         self.consume(TokenType::SEMICOLON, "Expect ';' after expression.")?;
-        return Ok(Stmt::Expression(Expression::new(expr)));
+        return Ok(Stmt::Expression(Expression::new(
+            expr,
+            self.span_from(start),
+            self.node_id(),
+        )));
     }
 
     fn function(&mut self, kind: String) -> Result<Stmt, ParseError> {
+        let start = self.previous().offset;
         let name = self
             .consume(TokenType::IDENTIFIER, &format!("Expect {kind} name."))?
             .clone();
@@ -224,9 +546,26 @@ impl Parser {
             TokenType::LEFT_BRACE,
             &format!("Expect '{{' before {kind} body."),
         )?;
-        let body = self.block()?;
+        let (mut body, value) = self.block_with_value()?;
+        // a trailing expression without a ';' is returned, as if written `return value;`
+        if let Some(value) = value {
+            let span = value.span();
+            let keyword = Token::synthetic(TokenType::RETURN, "return");
+            body.push(Stmt::Return(Return::new(
+                keyword,
+                Some(value),
+                span,
+                self.node_id(),
+            )));
+        }
 
-        return Ok(Stmt::Function(Function::new(name, parameters, body)));
+        return Ok(Stmt::Function(Function::new(
+            name,
+            parameters,
+            body,
+            self.span_from(start),
+            self.node_id(),
+        )));
     }
 
     fn expression(&mut self) -> Result<Expr, ParseError> {
@@ -234,6 +573,17 @@ impl Parser {
     }
 
     fn assignment(&mut self) -> Result<Expr, ParseError> {
+        if self.check(&TokenType::LEFT_BRACKET) && self.is_pattern_assignment() {
+            let pattern = self.pattern()?;
+            self.advance();
+            let value = self.assignment()?;
+            return Ok(Expr::Destructure(Destructure::new(
+                pattern,
+                value,
+                self.node_id(),
+            )));
+        }
+
         let expr = self.or()?;
         if self.r#match(&vec![TokenType::EQUAL]) {
             let equals = self.previous().clone();
@@ -241,11 +591,45 @@ impl Parser {
 
             match expr {
                 Expr::Variable(variable) => {
-                    return Ok(Expr::Assign(Assign::new(variable.name, value)));
+                    return Ok(Expr::Assign(Assign::new(
+                        variable.name,
+                        value,
+                        self.node_id(),
+                    )));
                 }
                 _ => {}
             }
 
+            self.error(&equals, "Invalid assignment target.");
+        } else if self.r#match(&vec![TokenType::AND_EQUAL, TokenType::OR_EQUAL]) {
+            let equals = self.previous().clone();
+            let value = self.assignment()?;
+
+            // `x or= y` is `x = x or y`, so the right side still short-circuits
+            if let Expr::Variable(variable) = expr {
+                let (r#type, lexeme) = if equals.r#type == TokenType::AND_EQUAL {
+                    (TokenType::AND, "and")
+                } else {
+                    (TokenType::OR, "or")
+                };
+                let operator = Token::new(
+                    r#type,
+                    lexeme.to_string(),
+                    None,
+                    equals.line,
+                    equals.column,
+                    equals.offset,
+                );
+                let name = variable.name.clone();
+                let logical = Expr::Logical(Logical::new(
+                    Expr::Variable(variable),
+                    operator,
+                    value,
+                    self.node_id(),
+                ));
+                return Ok(Expr::Assign(Assign::new(name, logical, self.node_id())));
+            }
+
             self.error(&equals, "Invalid assignment target.");
         }
 
@@ -255,10 +639,11 @@ impl Parser {
     fn or(&mut self) -> Result<Expr, ParseError> {
         let mut expr = self.and()?;
 
-        while self.r#match(&vec![TokenType::OR]) {
+        // '??' shares 'or' precedence, it only differs in what counts as missing
+        while self.r#match(&vec![TokenType::OR, TokenType::QUESTION_QUESTION]) {
             let operator: Token = self.previous().clone();
             let right = self.and()?;
-            expr = Expr::Logical(Logical::new(expr, operator, right));
+            expr = Expr::Logical(Logical::new(expr, operator, right, self.node_id()));
         }
 
         return Ok(expr);
@@ -270,26 +655,64 @@ impl Parser {
         while self.r#match(&vec![TokenType::AND]) {
             let operator: Token = self.previous().clone();
             let right = self.equality()?;
-            expr = Expr::Logical(Logical::new(expr, operator, right));
+            expr = Expr::Logical(Logical::new(expr, operator, right, self.node_id()));
         }
 
         return Ok(expr);
     }
 
     fn equality(&mut self) -> Result<Expr, ParseError> {
-        let mut expr: Expr = self.comparison()?;
+        let mut expr: Expr = self.bit_or()?;
 
         while self.r#match(&vec![TokenType::BANG_EQUAL, TokenType::EQUAL_EQUAL]) {
+            let operator: Token = self.previous().clone();
+            let right: Expr = self.bit_or()?;
+            expr = Expr::Binary(Binary::new(expr, operator, right, self.node_id()));
+        }
+
+        return Ok(expr);
+    }
+
+    // bitwise operators sit between equality and comparison so 'x & 1 == 0' groups as
+    // '(x & 1) == 0', keeping c's relative order of '|' < '^' < '&'
+    fn bit_or(&mut self) -> Result<Expr, ParseError> {
+        let mut expr: Expr = self.bit_xor()?;
+
+        while self.r#match(&vec![TokenType::PIPE]) {
+            let operator: Token = self.previous().clone();
+            let right: Expr = self.bit_xor()?;
+            expr = Expr::Binary(Binary::new(expr, operator, right, self.node_id()));
+        }
+
+        return Ok(expr);
+    }
+
+    fn bit_xor(&mut self) -> Result<Expr, ParseError> {
+        let mut expr: Expr = self.bit_and()?;
+
+        while self.r#match(&vec![TokenType::CARET]) {
+            let operator: Token = self.previous().clone();
+            let right: Expr = self.bit_and()?;
+            expr = Expr::Binary(Binary::new(expr, operator, right, self.node_id()));
+        }
+
+        return Ok(expr);
+    }
+
+    fn bit_and(&mut self) -> Result<Expr, ParseError> {
+        let mut expr: Expr = self.comparison()?;
+
+        while self.r#match(&vec![TokenType::AMPERSAND]) {
             let operator: Token = self.previous().clone();
             let right: Expr = self.comparison()?;
-            expr = Expr::Binary(Binary::new(expr, operator, right));
+            expr = Expr::Binary(Binary::new(expr, operator, right, self.node_id()));
         }
 
         return Ok(expr);
     }
 
     fn comparison(&mut self) -> Result<Expr, ParseError> {
-        let mut expr: Expr = self.term()?;
+        let mut expr: Expr = self.shift()?;
 
         while self.r#match(&vec![
             TokenType::GREATER,
@@ -297,9 +720,29 @@ impl Parser {
             TokenType::LESS,
             TokenType::LESS_EQUAL,
         ]) {
+            let operator: Token = self.previous().clone();
+            // `a < b < c` would compare a boolean with c, which is never what was meant.
+            // reported without unwinding, like an invalid assignment target
+            if matches!(&expr, Expr::Binary(binary) if is_comparison(&binary.operator)) {
+                self.error(
+                    &operator,
+                    "Chained comparison is not allowed; use explicit parentheses or &&.",
+                );
+            }
+            let right: Expr = self.shift()?;
+            expr = Expr::Binary(Binary::new(expr, operator, right, self.node_id()));
+        }
+
+        return Ok(expr);
+    }
+
+    fn shift(&mut self) -> Result<Expr, ParseError> {
+        let mut expr: Expr = self.term()?;
+
+        while self.r#match(&vec![TokenType::LESS_LESS, TokenType::GREATER_GREATER]) {
             let operator: Token = self.previous().clone();
             let right: Expr = self.term()?;
-            expr = Expr::Binary(Binary::new(expr, operator, right));
+            expr = Expr::Binary(Binary::new(expr, operator, right, self.node_id()));
         }
 
         return Ok(expr);
@@ -311,7 +754,7 @@ impl Parser {
         while self.r#match(&vec![TokenType::MINUS, TokenType::PLUS]) {
             let operator: Token = self.previous().clone();
             let right: Expr = self.factor()?;
-            expr = Expr::Binary(Binary::new(expr, operator, right));
+            expr = Expr::Binary(Binary::new(expr, operator, right, self.node_id()));
         }
 
         return Ok(expr);
@@ -323,17 +766,21 @@ impl Parser {
         while self.r#match(&vec![TokenType::SLASH, TokenType::STAR]) {
             let operator: Token = self.previous().clone();
             let right: Expr = self.unary()?;
-            expr = Expr::Binary(Binary::new(expr, operator, right));
+            expr = Expr::Binary(Binary::new(expr, operator, right, self.node_id()));
         }
 
         return Ok(expr);
     }
 
     fn unary(&mut self) -> Result<Expr, ParseError> {
-        if self.r#match(&vec![TokenType::BANG, TokenType::MINUS]) {
+        // there's no prefix decrement, so `--x` is still a double negation
+        if self.check(&TokenType::MINUS_MINUS) {
+            self.split_minus_minus();
+        }
+        if self.r#match(&vec![TokenType::BANG, TokenType::MINUS, TokenType::TILDE]) {
             let operator: Token = self.previous().clone();
             let right: Expr = self.unary()?;
-            return Ok(Expr::Unary(Unary::new(operator, right)));
+            return Ok(Expr::Unary(Unary::new(operator, right, self.node_id())));
         }
         return self.call();
     }
@@ -344,11 +791,31 @@ impl Parser {
         loop {
             if self.r#match(&vec![TokenType::LEFT_PAREN]) {
                 expr = self.finish_call(expr)?;
+            } else if self.r#match(&vec![TokenType::LEFT_BRACKET]) {
+                expr = self.finish_index(expr)?;
             } else {
                 break;
             }
         }
 
+        // a decrement is never followed by an operand, so `1--1` is still a minus and a negation
+        if self.check(&TokenType::MINUS_MINUS) && self.operand_follows() {
+            self.split_minus_minus();
+            return Ok(expr);
+        }
+
+        if self.r#match(&vec![TokenType::PLUS_PLUS, TokenType::MINUS_MINUS]) {
+            let operator = self.previous().clone();
+            if let Expr::Variable(variable) = expr {
+                return Ok(Expr::Postfix(Postfix::new(
+                    variable.name,
+                    operator,
+                    self.node_id(),
+                )));
+            }
+            self.error(&operator, "Invalid increment target.");
+        }
+
         return Ok(expr);
     }
 
@@ -370,37 +837,175 @@ impl Parser {
 
         let paren = self.consume(TokenType::RIGHT_PAREN, "Expect ')' after arguments.")?;
 
-        return Ok(Expr::Call(Call::new(callee, paren.to_owned(), arguments)));
+        return Ok(Expr::Call(Call::new(
+            callee,
+            paren.to_owned(),
+            arguments,
+            self.node_id(),
+        )));
+    }
+
+    fn finish_index(&mut self, object: Expr) -> Result<Expr, ParseError> {
+        let mut start = None;
+        if !self.check(&TokenType::COLON) {
+            start = Some(self.expression()?);
+        }
+
+        if !self.r#match(&vec![TokenType::COLON]) {
+            let bracket = self.consume(TokenType::RIGHT_BRACKET, "Expect ']' after index.")?;
+            // start can only be missing when a ':' follows it
+            return Ok(Expr::Index(Index::new(
+                object,
+                bracket.to_owned(),
+                start.unwrap(),
+                self.node_id(),
+            )));
+        }
+
+        let mut end = None;
+        if !self.check(&TokenType::RIGHT_BRACKET) {
+            end = Some(self.expression()?);
+        }
+        let bracket = self.consume(TokenType::RIGHT_BRACKET, "Expect ']' after slice.")?;
+
+        return Ok(Expr::Slice(Slice::new(
+            object,
+            bracket.to_owned(),
+            start,
+            end,
+            self.node_id(),
+        )));
     }
 
     fn primary(&mut self) -> Result<Expr, ParseError> {
+        let start = self.peek().offset;
         if self.r#match(&vec![TokenType::FALSE]) {
-            return Ok(Expr::Literal(Literal::new(Some(LiteralValue::Boolean(
-                false,
-            )))));
+            return Ok(Expr::Literal(Literal::new(
+                Some(LiteralValue::Boolean(false)),
+                self.span_from(start),
+                self.node_id(),
+            )));
         }
         if self.r#match(&vec![TokenType::TRUE]) {
-            return Ok(Expr::Literal(Literal::new(Some(LiteralValue::Boolean(
-                true,
-            )))));
+            return Ok(Expr::Literal(Literal::new(
+                Some(LiteralValue::Boolean(true)),
+                self.span_from(start),
+                self.node_id(),
+            )));
         }
         if self.r#match(&vec![TokenType::NIL]) {
-            return Ok(Expr::Literal(Literal::new(None)));
+            return Ok(Expr::Literal(Literal::new(
+                None,
+                self.span_from(start),
+                self.node_id(),
+            )));
         }
         if self.r#match(&vec![TokenType::NUMBER, TokenType::STRING]) {
-            return Ok(Expr::Literal(Literal::new(self.previous().literal.clone())));
+            return Ok(Expr::Literal(Literal::new(
+                self.previous().literal.clone(),
+                self.span_from(start),
+                self.node_id(),
+            )));
         }
         if self.r#match(&vec![TokenType::IDENTIFIER]) {
-            return Ok(Expr::Variable(Variable::new(self.previous().clone())));
+            return Ok(Expr::Variable(Variable::new(
+                self.previous().clone(),
+                self.node_id(),
+            )));
         }
         if self.r#match(&vec![TokenType::LEFT_PAREN]) {
             let expr: Expr = self.expression()?;
             self.consume(TokenType::RIGHT_PAREN, "Expect ')' after expressions.")?;
-            return Ok(Expr::Grouping(Grouping::new(expr)));
+            return Ok(Expr::Grouping(Grouping::new(
+                expr,
+                self.span_from(start),
+                self.node_id(),
+            )));
+        }
+        if self.r#match(&vec![TokenType::LEFT_BRACKET]) {
+            return self.list(start);
+        }
+        if self.r#match(&vec![TokenType::LEFT_BRACE]) {
+            return self.block_expr(start);
         }
         return Err(self.error(self.peek(), "Expect expression."));
     }
 
+    fn block_expr(&mut self, start: usize) -> Result<Expr, ParseError> {
+        let (statements, value) = self.block_with_value()?;
+        return Ok(Expr::Block(expr::Block::new(
+            statements,
+            value,
+            self.span_from(start),
+            self.node_id(),
+        )));
+    }
+
+    // like block, but an expression right before the '}' without a ';' is returned separately
+    fn block_with_value(&mut self) -> Result<(Vec<Stmt>, Option<Expr>), ParseError> {
+        let mut statements: Vec<Stmt> = Vec::new();
+        let mut value = None;
+
+        while !self.check(&TokenType::RIGHT_BRACE) && !self.is_at_end() {
+            if self.check_any(&[
+                TokenType::FUN,
+                TokenType::VAR,
+                TokenType::CONST,
+                TokenType::ASSERT,
+                TokenType::FOR,
+                TokenType::IF,
+                TokenType::UNLESS,
+                TokenType::PRINT,
+                TokenType::REPEAT,
+                TokenType::RETURN,
+                TokenType::WHILE,
+                TokenType::LEFT_BRACE,
+            ]) {
+                statements.push(self.declaration()?);
+                continue;
+            }
+
+            let expression_start = self.peek().offset;
+            let expr = self.expression()?;
+            if self.check(&TokenType::RIGHT_BRACE) {
+                value = Some(expr);
+                break;
+            }
+            self.consume(TokenType::SEMICOLON, "Expect ';' after expression.")?;
+            statements.push(Stmt::Expression(Expression::new(
+                expr,
+                self.span_from(expression_start),
+                self.node_id(),
+            )));
+        }
+
+        self.consume(TokenType::RIGHT_BRACE, "Expect '}' after block.")?;
+        return Ok((statements, value));
+    }
+
+    fn list(&mut self, start: usize) -> Result<Expr, ParseError> {
+        let bracket = self.previous().clone();
+        let mut elements = Vec::new();
+
+        if !self.check(&TokenType::RIGHT_BRACKET) {
+            loop {
+                elements.push(self.expression()?);
+
+                if !self.r#match(&vec![TokenType::COMMA]) {
+                    break;
+                }
+            }
+        }
+
+        self.consume(TokenType::RIGHT_BRACKET, "Expect ']' after list elements.")?;
+        return Ok(Expr::List(List::new(
+            bracket,
+            elements,
+            self.span_from(start),
+            self.node_id(),
+        )));
+    }
+
     fn r#match(&mut self, types: &Vec<TokenType>) -> bool {
         for r#type in types {
             if self.check(r#type) {
@@ -425,6 +1030,54 @@ impl Parser {
         return &self.peek().r#type == r#type;
     }
 
+    fn check_any(&self, types: &[TokenType]) -> bool {
+        return types.iter().any(|r#type| self.check(r#type));
+    }
+
+    fn check_next(&self, r#type: &TokenType) -> bool {
+        return match self.tokens.get(self.current + 1) {
+            Some(token) => &token.r#type == r#type,
+            None => false,
+        };
+    }
+
+    // whether the token after the current one can start an operand
+    fn operand_follows(&self) -> bool {
+        return [
+            TokenType::NUMBER,
+            TokenType::STRING,
+            TokenType::IDENTIFIER,
+            TokenType::TRUE,
+            TokenType::FALSE,
+            TokenType::NIL,
+            TokenType::LEFT_PAREN,
+            TokenType::LEFT_BRACKET,
+            TokenType::BANG,
+            TokenType::TILDE,
+        ]
+        .iter()
+        .any(|r#type| self.check_next(r#type));
+    }
+
+    // turns the current '--' back into the two '-' it was scanned from
+    fn split_minus_minus(&mut self) {
+        let token = self.peek().clone();
+        let minus = |column, offset| {
+            Token::new(
+                TokenType::MINUS,
+                "-".to_string(),
+                None,
+                token.line,
+                column,
+                offset,
+            )
+        };
+        let first = minus(token.column, token.offset);
+        let second = minus(token.column + 1, token.offset + 1);
+        self.tokens
+            .splice(self.current..=self.current, [first, second]);
+    }
+
     fn advance(&mut self) -> &Token {
         if !self.is_at_end() {
             self.current += 1;
@@ -444,9 +1097,17 @@ impl Parser {
         return self.tokens.get(self.current - 1).unwrap();
     }
 
+    fn span_from(&self, start: usize) -> (usize, usize) {
+        return (start, self.previous().end());
+    }
+
     fn error(&self, token: &Token, message: &str) -> ParseError {
-        error_token(token, message.to_string());
-        return ParseError::new();
+        let error = ParseError::new(token, message);
+        if self.reporting {
+            error_token(&error.token, error.message.clone());
+        }
+        self.errors.borrow_mut().push(error.clone());
+        return error;
     }
 
     fn synchronize(&mut self) {
@@ -458,13 +1119,17 @@ impl Parser {
             }
 
             match self.peek().r#type {
-                TokenType::CLASS
+                TokenType::ASSERT
+                | TokenType::CLASS
+                | TokenType::CONST
                 | TokenType::FUN
                 | TokenType::VAR
                 | TokenType::FOR
                 | TokenType::IF
+                | TokenType::UNLESS
                 | TokenType::WHILE
                 | TokenType::PRINT
+                | TokenType::REPEAT
                 | TokenType::RETURN => return,
                 _ => {
                     self.advance();
@@ -473,3 +1138,94 @@ impl Parser {
         }
     }
 }
+
+fn is_comparison(operator: &Token) -> bool {
+    return matches!(
+        operator.r#type,
+        TokenType::GREATER | TokenType::GREATER_EQUAL | TokenType::LESS | TokenType::LESS_EQUAL
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_program;
+    use crate::{expr::Expr, scanner::line_column, stmt::Stmt, test_support::result};
+
+    #[test]
+    fn constants_need_an_initializer() {
+        let errors = parse_program("const a;").unwrap_err();
+        assert_eq!(errors[0].message, "Expect '=' after constant name.");
+    }
+
+    #[test]
+    fn chained_comparisons_are_rejected() {
+        let errors = parse_program("print a < b < c;").unwrap_err();
+        assert!(errors[0]
+            .message
+            .starts_with("Chained comparison is not allowed"));
+        assert_eq!(errors[0].token.lexeme, "<");
+        assert!(parse_program("print (a < b) < c;").is_ok());
+    }
+
+    #[test]
+    fn try_parse_collects_every_error() {
+        let errors = parse_program("print ;\nvar = 1;\nprint 1;").unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|error| error.message.as_str()).collect();
+        assert_eq!(messages, ["Expect expression.", "Expect variable name."]);
+        assert_eq!(errors[1].token.line, 2);
+    }
+
+    #[test]
+    fn parse_errors_carry_the_offending_token() {
+        let errors = parse_program("var x = (1 + ;").unwrap_err();
+        assert_eq!(errors[0].message, "Expect expression.");
+        assert_eq!(errors[0].token.lexeme, ";");
+        assert_eq!((errors[0].token.line, errors[0].token.column), (1, 14));
+    }
+
+    #[test]
+    fn unless_negates_its_whole_condition() {
+        let statements = parse_program("unless (a or b) print 1;").unwrap();
+        let condition = match &statements[0] {
+            Stmt::If(r#if) => r#if.condition.as_ref(),
+            statement => panic!("expected an if, got {:?}", statement),
+        };
+        let negated = match condition {
+            Expr::Unary(unary) => unary.right.as_ref(),
+            condition => panic!("expected a negation, got {:?}", condition),
+        };
+        match negated {
+            Expr::Grouping(grouping) => assert!(matches!(*grouping.expression, Expr::Logical(_))),
+            negated => panic!("expected a grouping, got {:?}", negated),
+        }
+    }
+
+    #[test]
+    fn minus_minus_between_operands_is_subtracting_a_negation() {
+        assert_eq!(result("var result = 1--1;"), "2");
+        assert_eq!(result("var x = 5; var result = x--2;"), "7");
+        assert_eq!(result("var result = --3;"), "3");
+        assert_eq!(result("var x = 5; x--; var result = x;"), "4");
+    }
+
+    #[test]
+    fn only_variables_can_be_incremented() {
+        let errors = parse_program("(1 + 2)++;").unwrap_err();
+        assert_eq!(errors[0].message, "Invalid increment target.");
+    }
+
+    #[test]
+    fn multi_line_expressions_span_all_their_lines() {
+        let source = "print 1 +\n  2 *\n  3;";
+        let statements = parse_program(source).unwrap();
+        let expression = match &statements[0] {
+            Stmt::Print(print) => print.expression.as_ref(),
+            statement => panic!("expected a print, got {:?}", statement),
+        };
+        assert_eq!(expression.span(), (6, 19));
+        assert_eq!(&source[6..19], "1 +\n  2 *\n  3");
+        assert_eq!(line_column(source, 6, 4), (1, 7));
+        assert_eq!(line_column(source, 12, 4), (2, 3));
+        assert_eq!(line_column(source, 19, 4), (3, 4));
+    }
+}
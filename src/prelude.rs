@@ -0,0 +1,88 @@
+use crate::{
+    expr::NodeId, interpreter::Interpreter, parser::Parser, scanner::Scanner, token::Token,
+};
+
+// list helpers written in lox itself, loaded into the globals before the user's program
+const PRELUDE: &str = r#"
+fun map(list, function) {
+  var result = [];
+  for (element in list) push(result, function(element));
+  return result;
+}
+
+fun filter(list, predicate) {
+  var result = [];
+  for (element in list) if (predicate(element)) push(result, element);
+  return result;
+}
+
+fun reduce(list, function, initial) {
+  var accumulator = initial;
+  for (element in list) accumulator = function(accumulator, element);
+  return accumulator;
+}
+"#;
+
+// the user's program is numbered from 0, so the prelude takes ids far above anything it reaches
+const PRELUDE_FIRST_ID: NodeId = NodeId::MAX / 2;
+
+pub fn load(interpreter: &mut Interpreter) {
+    let tokens = Scanner::new(PRELUDE.to_string())
+        .try_scan_tokens()
+        .expect("the prelude should scan");
+    // no positions, so runtime errors inside the prelude don't point into the user's source
+    let tokens = tokens
+        .into_iter()
+        .map(|token| Token {
+            line: 0,
+            column: 0,
+            offset: 0,
+            ..token
+        })
+        .collect();
+    let statements = Parser::with_first_id(tokens, PRELUDE_FIRST_ID)
+        .try_parse()
+        .expect("the prelude should parse");
+    interpreter.interpret(statements);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::load;
+    use crate::{
+        interpreter::{Interpreter, RuntimeError},
+        natives::Capabilities,
+        parser::parse_program,
+        test_support::global,
+    };
+
+    fn run_with_prelude(source: &str) -> Result<Interpreter, RuntimeError> {
+        let mut interpreter = Interpreter::new(Capabilities::new());
+        load(&mut interpreter);
+        interpreter.try_interpret(parse_program(source).unwrap())?;
+        return Ok(interpreter);
+    }
+
+    #[test]
+    fn user_programs_can_call_the_prelude() {
+        let source = "
+            fun double(x) { return x * 2; }
+            fun small(x) { return x < 3; }
+            fun add(a, b) { return a + b; }
+            var result = [map([1, 2, 3], double), filter([1, 2, 3], small), reduce([1, 2, 3], add, 0)];
+        ";
+        let interpreter = run_with_prelude(source).unwrap();
+        let result = interpreter.stringify(&global(&interpreter, "result"));
+        assert_eq!(result, "[[2, 4, 6], [1, 2], 6]");
+    }
+
+    #[test]
+    fn errors_inside_the_prelude_have_no_source_position() {
+        let error = match run_with_prelude("var x = 1;\nmap(5, str);") {
+            Ok(_) => panic!("mapping over a number should fail"),
+            Err(error) => error,
+        };
+        assert_eq!(error.token.line, 0);
+        assert_eq!(error.token.column, 0);
+    }
+}
@@ -2,6 +2,7 @@ use std::collections::HashMap;
 
 use crate::{
     error,
+    parser::ParseError,
     token::{LiteralValue, Token},
     token_type::TokenType,
 };
@@ -11,47 +12,92 @@ pub struct Scanner {
     tokens: Vec<Token>,
     start: usize,
     current: usize,
+    // byte offsets alongside the char indices above, for token spans
+    start_offset: usize,
+    current_offset: usize,
     line: u64,
+    // char index where the current line begins, and the column the current token starts at
+    line_start: usize,
+    start_column: usize,
+    // columns a tab advances to the next stop by
+    tab_width: usize,
     keywords: HashMap<String, TokenType>,
+    // every error so far, and whether they also go to stderr and the global error flag
+    errors: Vec<ParseError>,
+    reporting: bool,
 }
 
 impl Scanner {
     pub fn new(source: String) -> Scanner {
+        return Scanner::with_tab_width(source, DEFAULT_TAB_WIDTH);
+    }
+
+    pub fn with_tab_width(source: String, tab_width: usize) -> Scanner {
         return Scanner {
             source: source.chars().collect(),
             tokens: Vec::new(),
             start: 0,
             current: 0,
+            start_offset: 0,
+            current_offset: 0,
             line: 1,
+            line_start: 0,
+            start_column: 1,
+            tab_width,
+            errors: Vec::new(),
+            reporting: true,
             keywords: HashMap::from([
                 ("and".to_string(), TokenType::AND),
+                ("assert".to_string(), TokenType::ASSERT),
                 ("class".to_string(), TokenType::CLASS),
+                ("const".to_string(), TokenType::CONST),
                 ("else".to_string(), TokenType::ELSE),
                 ("false".to_string(), TokenType::FALSE),
                 ("for".to_string(), TokenType::FOR),
                 ("fun".to_string(), TokenType::FUN),
                 ("if".to_string(), TokenType::IF),
+                ("in".to_string(), TokenType::IN),
                 ("nil".to_string(), TokenType::NIL),
                 ("or".to_string(), TokenType::OR),
                 ("print".to_string(), TokenType::PRINT),
+                ("repeat".to_string(), TokenType::REPEAT),
                 ("return".to_string(), TokenType::RETURN),
                 ("super".to_string(), TokenType::SUPER),
                 ("this".to_string(), TokenType::THIS),
                 ("true".to_string(), TokenType::TRUE),
+                ("unless".to_string(), TokenType::UNLESS),
                 ("var".to_string(), TokenType::VAR),
                 ("while".to_string(), TokenType::WHILE),
             ]),
         };
     }
 
+    // like scan_tokens, but quietly collects the errors and returns them instead of any tokens
+    pub fn try_scan_tokens(&mut self) -> Result<Vec<Token>, Vec<ParseError>> {
+        self.reporting = false;
+        self.scan_tokens();
+        if !self.errors.is_empty() {
+            return Err(std::mem::take(&mut self.errors));
+        }
+        return Ok(std::mem::take(&mut self.tokens));
+    }
+
     pub fn scan_tokens(&mut self) -> &Vec<Token> {
         while !self.is_at_end() {
             self.start = self.current;
+            self.start_offset = self.current_offset;
+            self.start_column = self.column_at(self.start);
             self.scan_token();
         }
 
-        self.tokens
-            .push(Token::new(TokenType::EOF, "".to_string(), None, self.line));
+        self.tokens.push(Token::new(
+            TokenType::EOF,
+            "".to_string(),
+            None,
+            self.line,
+            self.column_at(self.current),
+            self.current_offset,
+        ));
         return &self.tokens;
     }
 
@@ -62,12 +108,43 @@ impl Scanner {
             ')' => self.add_token(TokenType::RIGHT_PAREN, None),
             '{' => self.add_token(TokenType::LEFT_BRACE, None),
             '}' => self.add_token(TokenType::RIGHT_BRACE, None),
+            '[' => self.add_token(TokenType::LEFT_BRACKET, None),
+            ']' => self.add_token(TokenType::RIGHT_BRACKET, None),
+            ':' => self.add_token(TokenType::COLON, None),
             ',' => self.add_token(TokenType::COMMA, None),
-            '.' => self.add_token(TokenType::DOT, None),
-            '-' => self.add_token(TokenType::MINUS, None),
-            '+' => self.add_token(TokenType::PLUS, None),
+            '.' => {
+                if self.is_digit(self.peek()) {
+                    self.number();
+                } else if self.peek() == '.' && self.peek_next() == '.' {
+                    self.advance();
+                    self.advance();
+                    self.add_token(TokenType::DOT_DOT_DOT, None);
+                } else {
+                    self.add_token(TokenType::DOT, None);
+                }
+            }
+            '-' => {
+                let r#type = if self.r#match('-') {
+                    TokenType::MINUS_MINUS
+                } else {
+                    TokenType::MINUS
+                };
+                self.add_token(r#type, None);
+            }
+            '+' => {
+                let r#type = if self.r#match('+') {
+                    TokenType::PLUS_PLUS
+                } else {
+                    TokenType::PLUS
+                };
+                self.add_token(r#type, None);
+            }
             ';' => self.add_token(TokenType::SEMICOLON, None),
             '*' => self.add_token(TokenType::STAR, None),
+            '&' => self.add_token(TokenType::AMPERSAND, None),
+            '|' => self.add_token(TokenType::PIPE, None),
+            '^' => self.add_token(TokenType::CARET, None),
+            '~' => self.add_token(TokenType::TILDE, None),
             '!' => {
                 let r#type = if self.r#match('=') {
                     TokenType::BANG_EQUAL
@@ -87,6 +164,8 @@ impl Scanner {
             '<' => {
                 let r#type = if self.r#match('=') {
                     TokenType::LESS_EQUAL
+                } else if self.r#match('<') {
+                    TokenType::LESS_LESS
                 } else {
                     TokenType::LESS
                 };
@@ -95,30 +174,49 @@ impl Scanner {
             '>' => {
                 let r#type = if self.r#match('=') {
                     TokenType::GREATER_EQUAL
+                } else if self.r#match('>') {
+                    TokenType::GREATER_GREATER
                 } else {
                     TokenType::GREATER
                 };
                 self.add_token(r#type, None);
             }
+            '?' => {
+                if self.r#match('?') {
+                    self.add_token(TokenType::QUESTION_QUESTION, None);
+                } else {
+                    self.error("Unexpected character: ?".to_string());
+                }
+            }
             '/' => {
                 if self.r#match('/') {
-                    while self.peek() != '\n' && !self.is_at_end() {
+                    while self.peek() != '\n' && self.peek() != '\r' && !self.is_at_end() {
                         self.advance();
                     }
                 } else {
                     self.add_token(TokenType::SLASH, None);
                 }
             }
-            ' ' | '\r' | '\t' => {}
-            '"' => self.string(),
-            '\n' => self.line += 1,
+            ' ' | '\t' => {}
+            '"' => {
+                if self.peek() == '"' && self.peek_next() == '"' {
+                    self.raw_string();
+                } else {
+                    self.string();
+                }
+            }
+            '\n' | '\r' => {
+                if self.ends_line(char) {
+                    self.new_line();
+                }
+            }
             char => {
                 if self.is_digit(char) {
                     self.number();
                 } else if self.is_alpha(char) {
                     self.identifier();
                 } else {
-                    error(self.line, format!("Unexpected character: {char}"));
+                    self.error(format!("Unexpected character: {char}"));
                 }
             }
         }
@@ -130,51 +228,166 @@ impl Scanner {
         }
 
         let text: String = self.source[self.start..self.current].into_iter().collect();
-        let r#type = self.keywords.get(&text).unwrap_or(&TokenType::IDENTIFIER);
-        self.add_token(r#type.clone(), None);
+        let mut r#type = self
+            .keywords
+            .get(&text)
+            .unwrap_or(&TokenType::IDENTIFIER)
+            .clone();
+        // 'and=' and 'or=' are written without a space, '==' after them is still a comparison
+        if self.peek() == '=' && self.peek_next() != '=' {
+            let assignment = match r#type {
+                TokenType::AND => Some(TokenType::AND_EQUAL),
+                TokenType::OR => Some(TokenType::OR_EQUAL),
+                _ => None,
+            };
+            if assignment.is_some() {
+                self.advance();
+                r#type = assignment.unwrap();
+            }
+        }
+        self.add_token(r#type, None);
     }
 
+    // also called for a '.' followed by a digit, which is reported along with a
+    // trailing '.', since a dot touching a number can only be a mistyped decimal
     fn number(&mut self) {
+        let leading_dot = self.source[self.start] == '.';
         while self.is_digit(self.peek()) {
             self.advance();
         }
 
-        if self.peek() == '.' && self.is_digit(self.peek_next()) {
+        if !leading_dot && self.peek() == '.' && self.is_digit(self.peek_next()) {
             self.advance();
             while self.is_digit(self.peek()) {
                 self.advance();
             }
         }
 
+        let trailing_dot = self.peek() == '.';
+        if trailing_dot {
+            self.advance();
+        }
+        if leading_dot || trailing_dot {
+            self.error("Malformed number.".to_string());
+        }
+
         let substring: String = self.source[self.start..self.current].into_iter().collect();
-        self.add_token(
-            TokenType::NUMBER,
-            Some(LiteralValue::Number(
-                str::parse::<f64>(substring.as_str()).unwrap(),
-            )),
-        )
+        // still emit the number so the parser doesn't report errors of its own
+        let mut digits = substring.trim_end_matches('.').to_string();
+        if leading_dot {
+            digits.insert(0, '0');
+        }
+        // literals without a '.' are integers, unless they're too big for one
+        let literal = match str::parse::<i64>(digits.as_str()) {
+            Ok(integer) => LiteralValue::Integer(integer),
+            Err(_) => LiteralValue::Number(str::parse::<f64>(digits.as_str()).unwrap()),
+        };
+        self.add_token(TokenType::NUMBER, Some(literal))
     }
 
     fn string(&mut self) {
         while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.line += 1;
+            let char = self.advance();
+            if self.ends_line(char) {
+                self.new_line();
             }
-            self.advance();
         }
         if self.is_at_end() {
-            error(self.line, "Unterminated string.".to_string());
+            self.error("Unterminated string.".to_string());
             return;
         }
 
         self.advance();
 
-        let value: String = self.source[(self.start + 1)..(self.current - 1)]
-            .into_iter()
+        // the lexeme keeps the escapes as written, only the literal is decoded
+        let raw = &self.source[(self.start + 1)..(self.current - 1)];
+        let value = match self.unescape(raw) {
+            Ok(value) => value,
+            Err(message) => {
+                let raw = raw.iter().collect();
+                self.error(message);
+                // still emit the string so the parser doesn't report errors of its own
+                raw
+            }
+        };
+        self.add_token(TokenType::STRING, Some(LiteralValue::String(value)));
+    }
+
+    // a """...""" string, kept verbatim: no escapes, and quotes and newlines are allowed inside
+    fn raw_string(&mut self) {
+        self.advance();
+        self.advance();
+        while !self.is_at_end() && !self.at_triple_quote() {
+            let char = self.advance();
+            if self.ends_line(char) {
+                self.new_line();
+            }
+        }
+        if self.is_at_end() {
+            self.error("Unterminated triple-quoted string.".to_string());
+            return;
+        }
+
+        self.advance();
+        self.advance();
+        self.advance();
+
+        let value: String = self.source[(self.start + 3)..(self.current - 3)]
+            .iter()
             .collect();
         self.add_token(TokenType::STRING, Some(LiteralValue::String(value)));
     }
 
+    fn at_triple_quote(&self) -> bool {
+        return self.source[self.current..].starts_with(&['"', '"', '"']);
+    }
+
+    // decodes '\xNN' and '\u{N...}' escapes, leaving any other backslash as is
+    fn unescape(&self, raw: &[char]) -> Result<String, String> {
+        let mut value = String::new();
+        let mut i = 0;
+        while i < raw.len() {
+            let char = raw[i];
+            i += 1;
+            if char != '\\' || i >= raw.len() || (raw[i] != 'x' && raw[i] != 'u') {
+                value.push(char);
+                continue;
+            }
+
+            let digits: String;
+            if raw[i] == 'x' {
+                digits = raw[(i + 1)..raw.len().min(i + 3)].iter().collect();
+                if digits.len() != 2 {
+                    return Err("Invalid '\\x' escape: expected two hex digits.".to_string());
+                }
+                i += 3;
+            } else {
+                if raw.get(i + 1) != Some(&'{') {
+                    return Err("Invalid '\\u' escape: expected '{'.".to_string());
+                }
+                let close = raw[i..].iter().position(|c| *c == '}');
+                if close.is_none() {
+                    return Err("Invalid '\\u' escape: expected '}'.".to_string());
+                }
+                digits = raw[(i + 2)..(i + close.unwrap())].iter().collect();
+                if digits.is_empty() || digits.len() > 6 {
+                    return Err("Invalid '\\u' escape: expected 1 to 6 hex digits.".to_string());
+                }
+                i += close.unwrap() + 1;
+            }
+
+            if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(format!("Invalid hex digits in escape: {digits}"));
+            }
+            let code_point = u32::from_str_radix(&digits, 16).unwrap();
+            match char::from_u32(code_point) {
+                Some(decoded) => value.push(decoded),
+                None => return Err(format!("Invalid code point in escape: {digits}")),
+            }
+        }
+        return Ok(value);
+    }
+
     fn r#match(&mut self, expected: char) -> bool {
         if self.is_at_end() {
             return false;
@@ -184,6 +397,7 @@ impl Scanner {
         }
 
         self.current += 1;
+        self.current_offset += expected.len_utf8();
         return true;
     }
 
@@ -202,7 +416,8 @@ impl Scanner {
     }
 
     fn is_alpha(&self, c: char) -> bool {
-        return (c >= 'a' && c <= 'z') || (c >= 'A' && c <= 'Z') || c == '_';
+        // any unicode letter may start an identifier, digits stay ascii-only
+        return c.is_alphabetic() || c == '_';
     }
 
     fn is_alpha_numeric(&self, c: char) -> bool {
@@ -216,16 +431,185 @@ impl Scanner {
     fn advance(&mut self) -> char {
         let char = self.source[self.current];
         self.current += 1;
+        self.current_offset += char.len_utf8();
         return char;
     }
 
     fn add_token(&mut self, r#type: TokenType, literal: Option<LiteralValue>) {
         let text: String = self.source[self.start..self.current].into_iter().collect();
-        self.tokens
-            .push(Token::new(r#type, text, literal, self.line));
+        self.tokens.push(Token::new(
+            r#type,
+            text,
+            literal,
+            self.line,
+            self.start_column,
+            self.start_offset,
+        ));
+    }
+
+    // called with a just-consumed character. '\n', '\r\n' and a lone '\r' each end one line,
+    // so the '\r' of a '\r\n' pair is left for the '\n' to count
+    fn ends_line(&self, char: char) -> bool {
+        return char == '\n' || (char == '\r' && self.peek() != '\n');
+    }
+
+    // called after consuming the character that ends a line
+    fn new_line(&mut self) {
+        self.line += 1;
+        self.line_start = self.current;
+    }
+
+    // errors point at the start of the token being scanned
+    fn error(&mut self, message: String) {
+        if self.reporting {
+            error(self.line, self.start_column, message.clone());
+        }
+        let lexeme = self.source[self.start..self.current].iter().collect();
+        let token = Token::new(
+            TokenType::EOF,
+            lexeme,
+            None,
+            self.line,
+            self.start_column,
+            self.start_offset,
+        );
+        self.errors.push(ParseError::new(&token, &message));
+    }
+
+    // 1-based column of a char index on the current line
+    fn column_at(&self, index: usize) -> usize {
+        return self.source[self.line_start..index]
+            .iter()
+            .fold(1, |column, &char| next_column(column, char, self.tab_width));
     }
 
     fn is_at_end(&self) -> bool {
         return self.current >= self.source.len();
     }
 }
+
+pub const DEFAULT_TAB_WIDTH: usize = 4;
+
+// the column after a character, with tabs jumping to the next multiple of tab_width
+pub fn next_column(column: usize, char: char, tab_width: usize) -> usize {
+    if char == '\t' && tab_width > 0 {
+        return (column - 1) / tab_width * tab_width + tab_width + 1;
+    }
+    return column + 1;
+}
+
+// maps a byte offset back to a 1-based (line, column) pair, counting columns the way the scanner does
+pub fn line_column(source: &str, offset: usize, tab_width: usize) -> (u64, usize) {
+    let mut line = 1;
+    let mut column = 1;
+    for (index, char) in source.char_indices() {
+        if index >= offset {
+            break;
+        }
+        // same rule as Scanner::ends_line
+        let ends_line =
+            char == '\n' || (char == '\r' && source[index + 1..].chars().next() != Some('\n'));
+        if ends_line {
+            line += 1;
+            column = 1;
+        } else {
+            column = next_column(column, char, tab_width);
+        }
+    }
+    return (line, column);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Scanner;
+    use crate::{
+        token::{LiteralValue, Token},
+        token_type::TokenType,
+    };
+
+    fn scan(source: &str) -> Vec<Token> {
+        return Scanner::new(source.to_string())
+            .try_scan_tokens()
+            .expect("source should scan");
+    }
+
+    #[test]
+    fn identifiers_can_use_unicode_letters() {
+        let tokens = scan("var café = 1;");
+        assert_eq!(tokens[1].r#type, TokenType::IDENTIFIER);
+        assert_eq!(tokens[1].lexeme, "café");
+    }
+
+    #[test]
+    fn strings_keep_emoji_intact() {
+        let tokens = scan("\"☕🎉\"");
+        assert_eq!(tokens[0].r#type, TokenType::STRING);
+        assert_eq!(tokens[0].lexeme, "\"☕🎉\"");
+    }
+
+    fn string_literal(source: &str) -> Option<LiteralValue> {
+        return scan(source)[0].literal.clone();
+    }
+
+    #[test]
+    fn escapes_decode_bmp_and_astral_code_points() {
+        assert_eq!(
+            string_literal("\"\\x41\\u{e9}\""),
+            Some(LiteralValue::String("Aé".to_string()))
+        );
+        assert_eq!(
+            string_literal("\"\\u{1F600}\""),
+            Some(LiteralValue::String("😀".to_string()))
+        );
+        // the lexeme keeps the escape as written
+        assert_eq!(scan("\"\\u{1F600}\"")[0].lexeme, "\"\\u{1F600}\"");
+    }
+
+    #[test]
+    fn malformed_escapes_are_errors() {
+        assert_eq!(
+            scan_error("\"\\u{110000}\""),
+            "Invalid code point in escape: 110000"
+        );
+        assert_eq!(scan_error("\"\\xZZ\""), "Invalid hex digits in escape: ZZ");
+    }
+
+    fn lines(source: &str) -> Vec<u64> {
+        return scan(source).iter().map(|token| token.line).collect();
+    }
+
+    #[test]
+    fn crlf_and_lone_cr_both_end_a_line() {
+        assert_eq!(lines("a\r\nb\r\nc"), [1, 2, 3, 3]);
+        assert_eq!(lines("a\rb\rc"), [1, 2, 3, 3]);
+        assert_eq!(lines("a\r\n\rb"), [1, 3, 3]);
+        // offsets map back to the same lines errors are reported on
+        assert_eq!(super::line_column("a\r\nb", 3, 4), (2, 1));
+        assert_eq!(super::line_column("a\rb", 2, 4), (2, 1));
+    }
+
+    #[test]
+    fn triple_quoted_strings_keep_quotes_and_newlines() {
+        let tokens = scan("\"\"\"say \"hi\"\nbye\"\"\" x");
+        assert_eq!(
+            tokens[0].literal,
+            Some(LiteralValue::String("say \"hi\"\nbye".to_string()))
+        );
+        // lines inside the string still count
+        assert_eq!(tokens[1].line, 2);
+    }
+
+    fn scan_error(source: &str) -> String {
+        return match Scanner::new(source.to_string()).try_scan_tokens() {
+            Ok(_) => panic!("{:?} should not scan", source),
+            Err(errors) => errors[0].message.clone(),
+        };
+    }
+
+    #[test]
+    fn numbers_need_digits_on_both_sides_of_the_dot() {
+        assert_eq!(scan_error(".5"), "Malformed number.");
+        assert_eq!(scan_error("5."), "Malformed number.");
+        assert_eq!(scan("5.25")[0].literal, Some(LiteralValue::Number(5.25)));
+    }
+}
@@ -0,0 +1,593 @@
+use crate::{
+    expr::{self, Expr},
+    stmt::{self, Stmt},
+    token::{LiteralValue, Token},
+    token_type::TokenType,
+};
+
+const INDENT: &str = "  ";
+
+pub struct SourceFormatter {
+    depth: usize,
+}
+
+impl SourceFormatter {
+    pub fn new() -> SourceFormatter {
+        return SourceFormatter { depth: 0 };
+    }
+
+    pub fn format(&mut self, statements: &Vec<Stmt>) -> String {
+        let mut string = String::new();
+        for statement in statements {
+            string += self.line(statement).as_str();
+        }
+        return string;
+    }
+
+    fn indent(&self) -> String {
+        return INDENT.repeat(self.depth);
+    }
+
+    // renders a statement on its own line(s) at the current depth
+    fn line(&mut self, stmt: &Stmt) -> String {
+        return format!("{}{}\n", self.indent(), stmt.accept(self));
+    }
+
+    // renders the body of an if/while, keeping blocks on the same line as the header
+    fn body(&mut self, stmt: &Stmt) -> String {
+        if let Stmt::Block(_) = stmt {
+            return format!(" {}", stmt.accept(self));
+        }
+
+        self.depth += 1;
+        let string = format!("\n{}{}", self.indent(), stmt.accept(self));
+        self.depth -= 1;
+        return string;
+    }
+
+    fn block(&mut self, statements: &Vec<Stmt>) -> String {
+        if statements.is_empty() {
+            return "{}".to_string();
+        }
+
+        let mut string = "{\n".to_string();
+        self.depth += 1;
+        for statement in statements {
+            string += self.line(statement).as_str();
+        }
+        self.depth -= 1;
+        string += format!("{}}}", self.indent()).as_str();
+        return string;
+    }
+
+    // reassembles a for loop from the while it was desugared into
+    fn r#for(&mut self, initializer: Option<&Stmt>, r#while: &stmt::While) -> String {
+        let initializer = match initializer {
+            Some(initializer) => initializer.accept(self),
+            None => ";".to_string(),
+        };
+        let mut clauses = format!("{} {};", initializer, self.expr(&r#while.condition));
+        if r#while.increment.is_some() {
+            clauses += format!(" {}", self.expr(r#while.increment.as_ref().unwrap())).as_str();
+        }
+        return format!("for ({}){}", clauses, self.body(&r#while.body));
+    }
+
+    fn pattern(&mut self, pattern: &expr::Pattern) -> String {
+        let mut names: Vec<String> = pattern
+            .names
+            .iter()
+            .map(|name| name.lexeme.clone())
+            .collect();
+        if let Some(rest) = &pattern.rest {
+            names.push(format!("...{}", rest.lexeme));
+        }
+        return format!("[{}]", names.join(", "));
+    }
+
+    fn expr(&mut self, expr: &Expr) -> String {
+        return expr.accept(self);
+    }
+
+    // an operand, parenthesized if it binds looser than its position needs. parsed code keeps
+    // its own Grouping nodes, but desugared nodes like unless's negation don't have any
+    fn operand(&mut self, expr: &Expr, min: u8) -> String {
+        let string = self.expr(expr);
+        if precedence(expr) < min {
+            return format!("({})", string);
+        }
+        return string;
+    }
+
+    fn infix(&mut self, left: &Expr, operator: &Token, right: &Expr) -> String {
+        let level = operator_precedence(&operator.r#type);
+        // comparisons don't chain, so neither side can be another comparison
+        let left_min = if level == COMPARISON {
+            level + 1
+        } else {
+            level
+        };
+        return format!(
+            "{} {} {}",
+            self.operand(left, left_min),
+            operator.lexeme,
+            self.operand(right, level + 1)
+        );
+    }
+}
+
+// binding strength of each level of the grammar, loosest first
+const ASSIGNMENT: u8 = 1;
+const COMPARISON: u8 = 8;
+const UNARY: u8 = 12;
+const CALL: u8 = 13;
+const PRIMARY: u8 = 14;
+
+fn operator_precedence(r#type: &TokenType) -> u8 {
+    return match r#type {
+        TokenType::OR | TokenType::QUESTION_QUESTION => 2,
+        TokenType::AND => 3,
+        TokenType::BANG_EQUAL | TokenType::EQUAL_EQUAL => 4,
+        TokenType::PIPE => 5,
+        TokenType::CARET => 6,
+        TokenType::AMPERSAND => 7,
+        TokenType::GREATER | TokenType::GREATER_EQUAL | TokenType::LESS | TokenType::LESS_EQUAL => {
+            COMPARISON
+        }
+        TokenType::LESS_LESS | TokenType::GREATER_GREATER => 9,
+        TokenType::MINUS | TokenType::PLUS => 10,
+        _ => 11,
+    };
+}
+
+fn precedence(expr: &Expr) -> u8 {
+    return match expr {
+        Expr::Assign(_) | Expr::Destructure(_) => ASSIGNMENT,
+        Expr::Binary(binary) => operator_precedence(&binary.operator.r#type),
+        Expr::Logical(logical) => operator_precedence(&logical.operator.r#type),
+        Expr::Unary(_) => UNARY,
+        Expr::Call(_) | Expr::Index(_) | Expr::Slice(_) | Expr::Postfix(_) => CALL,
+        Expr::Block(_)
+        | Expr::Grouping(_)
+        | Expr::List(_)
+        | Expr::Literal(_)
+        | Expr::Variable(_) => PRIMARY,
+    };
+}
+
+impl stmt::Visitor for SourceFormatter {
+    type Output = String;
+
+    fn visit_assert(&mut self, assert: &stmt::Assert) -> Self::Output {
+        let condition = self.expr(&assert.condition);
+        if assert.message.is_none() {
+            return format!("assert({});", condition);
+        }
+        let message = self.expr(assert.message.as_ref().unwrap());
+        return format!("assert({}, {});", condition, message);
+    }
+
+    fn visit_block(&mut self, block: &stmt::Block) -> Self::Output {
+        if let [Stmt::Var(_), Stmt::While(r#while)] = block.statements.as_slice() {
+            if r#while.loop_variable.is_some() {
+                return self.r#for(block.statements.first(), r#while);
+            }
+        }
+        return self.block(&block.statements);
+    }
+
+    fn visit_destructure(&mut self, destructure: &stmt::Destructure) -> Self::Output {
+        return format!(
+            "var {} = {};",
+            self.pattern(&destructure.pattern),
+            self.expr(&destructure.initializer)
+        );
+    }
+
+    fn visit_expression(&mut self, expression: &stmt::Expression) -> Self::Output {
+        return format!("{};", self.expr(&expression.expression));
+    }
+
+    fn visit_for_each(&mut self, for_each: &stmt::ForEach) -> Self::Output {
+        return format!(
+            "for ({} in {}){}",
+            for_each.name.lexeme,
+            self.expr(&for_each.iterable),
+            self.body(&for_each.body)
+        );
+    }
+
+    fn visit_function(&mut self, function: &stmt::Function) -> Self::Output {
+        let params: Vec<String> = function
+            .params
+            .iter()
+            .map(|param| param.lexeme.clone())
+            .collect();
+        return format!(
+            "fun {}({}) {}",
+            function.name.lexeme,
+            params.join(", "),
+            self.block(&function.body)
+        );
+    }
+
+    fn visit_if(&mut self, r#if: &stmt::If) -> Self::Output {
+        let mut string = format!(
+            "if ({}){}",
+            self.expr(&r#if.condition),
+            self.body(&r#if.then_branch)
+        );
+        if r#if.else_branch.is_none() {
+            return string;
+        }
+
+        if let Stmt::Block(_) = r#if.then_branch.as_ref() {
+            string += " else";
+        } else {
+            string += format!("\n{}else", self.indent()).as_str();
+        }
+
+        let else_branch = r#if.else_branch.as_ref().unwrap();
+        if let Stmt::If(_) = else_branch.as_ref() {
+            // keep else-if chains flat
+            string += format!(" {}", else_branch.accept(self)).as_str();
+        } else {
+            string += self.body(else_branch).as_str();
+        }
+        return string;
+    }
+
+    fn visit_print(&mut self, print: &stmt::Print) -> Self::Output {
+        return format!("print {};", self.expr(&print.expression));
+    }
+
+    fn visit_repeat(&mut self, repeat: &stmt::Repeat) -> Self::Output {
+        return format!(
+            "repeat ({}){}",
+            self.expr(&repeat.count),
+            self.body(&repeat.body)
+        );
+    }
+
+    fn visit_return(&mut self, r#return: &stmt::Return) -> Self::Output {
+        if r#return.value.is_none() {
+            return "return;".to_string();
+        }
+        return format!("return {};", self.expr(r#return.value.as_ref().unwrap()));
+    }
+
+    fn visit_var(&mut self, var: &stmt::Var) -> Self::Output {
+        let keyword = if var.constant { "const" } else { "var" };
+        if var.initializer.is_none() {
+            return format!("{} {};", keyword, var.name.lexeme);
+        }
+        return format!(
+            "{} {} = {};",
+            keyword,
+            var.name.lexeme,
+            self.expr(var.initializer.as_ref().unwrap())
+        );
+    }
+
+    fn visit_while(&mut self, r#while: &stmt::While) -> Self::Output {
+        if r#while.increment.is_some() {
+            return self.r#for(None, r#while);
+        }
+        return format!(
+            "while ({}){}",
+            self.expr(&r#while.condition),
+            self.body(&r#while.body)
+        );
+    }
+}
+
+impl expr::Visitor for SourceFormatter {
+    type Output = String;
+
+    fn visit_assign(&mut self, assign: &expr::Assign) -> Self::Output {
+        return format!("{} = {}", assign.name.lexeme, self.expr(&assign.value));
+    }
+
+    fn visit_binary(&mut self, binary: &expr::Binary) -> Self::Output {
+        return self.infix(&binary.left, &binary.operator, &binary.right);
+    }
+
+    fn visit_block(&mut self, block: &expr::Block) -> Self::Output {
+        if block.value.is_none() {
+            return self.block(&block.statements);
+        }
+
+        let mut string = "{\n".to_string();
+        self.depth += 1;
+        for statement in &block.statements {
+            string += self.line(statement).as_str();
+        }
+        let value = self.expr(block.value.as_ref().unwrap());
+        string += format!("{}{}\n", self.indent(), value).as_str();
+        self.depth -= 1;
+        string += format!("{}}}", self.indent()).as_str();
+        return string;
+    }
+
+    fn visit_call(&mut self, call: &expr::Call) -> Self::Output {
+        let arguments: Vec<String> = call
+            .arguments
+            .iter()
+            .map(|argument| self.expr(argument))
+            .collect();
+        return format!(
+            "{}({})",
+            self.operand(&call.callee, CALL),
+            arguments.join(", ")
+        );
+    }
+
+    fn visit_destructure(&mut self, destructure: &expr::Destructure) -> Self::Output {
+        return format!(
+            "{} = {}",
+            self.pattern(&destructure.pattern),
+            self.expr(&destructure.value)
+        );
+    }
+
+    fn visit_grouping(&mut self, grouping: &expr::Grouping) -> Self::Output {
+        return format!("({})", self.expr(&grouping.expression));
+    }
+
+    fn visit_index(&mut self, index: &expr::Index) -> Self::Output {
+        return format!(
+            "{}[{}]",
+            self.operand(&index.object, CALL),
+            self.expr(&index.index)
+        );
+    }
+
+    fn visit_list(&mut self, list: &expr::List) -> Self::Output {
+        let elements: Vec<String> = list
+            .elements
+            .iter()
+            .map(|element| self.expr(element))
+            .collect();
+        return format!("[{}]", elements.join(", "));
+    }
+
+    fn visit_literal(&mut self, literal: &expr::Literal) -> Self::Output {
+        return match &literal.value {
+            None => "nil".to_string(),
+            Some(LiteralValue::String(value)) => quote(value),
+            // keep the `.0` so a whole float doesn't read back as an integer
+            Some(LiteralValue::Number(value)) if value.fract() == 0.0 => format!("{:.1}", value),
+            Some(LiteralValue::Number(value)) => format!("{}", value),
+            Some(LiteralValue::Integer(value)) => value.to_string(),
+            Some(value) => value.to_string(),
+        };
+    }
+
+    fn visit_logical(&mut self, logical: &expr::Logical) -> Self::Output {
+        return self.infix(&logical.left, &logical.operator, &logical.right);
+    }
+
+    fn visit_postfix(&mut self, postfix: &expr::Postfix) -> Self::Output {
+        return format!("{}{}", postfix.name.lexeme, postfix.operator.lexeme);
+    }
+
+    fn visit_slice(&mut self, slice: &expr::Slice) -> Self::Output {
+        let object = self.operand(&slice.object, CALL);
+        let start = slice
+            .start
+            .as_ref()
+            .map_or(String::new(), |start| self.expr(start));
+        let end = slice
+            .end
+            .as_ref()
+            .map_or(String::new(), |end| self.expr(end));
+        return format!("{}[{}:{}]", object, start, end);
+    }
+
+    fn visit_unary(&mut self, unary: &expr::Unary) -> Self::Output {
+        let right = self.operand(&unary.right, UNARY);
+        // `- -x` would otherwise scan back as the '--' operator
+        if let Expr::Unary(_) = unary.right.as_ref() {
+            return format!("{} {}", unary.operator.lexeme, right);
+        }
+        return format!("{}{}", unary.operator.lexeme, right);
+    }
+
+    fn visit_variable(&mut self, variable: &expr::Variable) -> Self::Output {
+        return variable.name.lexeme.clone();
+    }
+}
+
+// a string literal that scans back to the same value. quotes and backslashes are written as
+// hex escapes, since a triple-quoted string can't hold every value either
+fn quote(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for char in value.chars() {
+        match char {
+            '"' => quoted.push_str("\\x22"),
+            '\\' => quoted.push_str("\\x5c"),
+            char => quoted.push(char),
+        }
+    }
+    quoted.push('"');
+    return quoted;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SourceFormatter;
+    use crate::{
+        parser::parse_program,
+        test_support::{global, run},
+    };
+
+    fn format(source: &str) -> String {
+        let statements = parse_program(source).expect("test source should parse");
+        return SourceFormatter::new().format(&statements);
+    }
+
+    #[test]
+    fn functions_and_control_flow_are_laid_out_one_statement_per_line() {
+        let source = "fun add(a, b) { return a + b; } var i = 0; while (i < 3) { if (i == 1) print \"one\"; else { print i; } i = i + 1; } for (var j = 0; j < 2; j = j + 1) print j;";
+        let expected = "fun add(a, b) {
+  return a + b;
+}
+var i = 0;
+while (i < 3) {
+  if (i == 1)
+    print \"one\";
+  else {
+    print i;
+  }
+  i = i + 1;
+}
+for (var j = 0; j < 2; j = j + 1)
+  print j;
+";
+        assert_eq!(format(source), expected);
+    }
+
+    #[test]
+    fn negated_unless_condition_keeps_its_parentheses() {
+        assert!(format("unless (a or b) print 1;").starts_with("if (!(a or b))"));
+    }
+
+    #[test]
+    fn adjacent_unary_operators_are_spaced() {
+        assert_eq!(format("print - -1;").trim(), "print - -1;");
+        assert!(parse_program(&format("print - -1;")).is_ok());
+    }
+
+    #[test]
+    fn grouped_operands_stay_grouped() {
+        assert_eq!(format("print -(1 + 2) * 3;").trim(), "print -(1 + 2) * 3;");
+        assert_eq!(format("print 1 - (2 - 3);").trim(), "print 1 - (2 - 3);");
+    }
+
+    #[test]
+    fn whole_floats_keep_their_decimal_point() {
+        assert_eq!(format("print 2.0;").trim(), "print 2.0;");
+        assert_eq!(format("print 2.5;").trim(), "print 2.5;");
+        assert_eq!(format("print 2;").trim(), "print 2;");
+    }
+
+    // what a program leaves in `result`, or the error it stops with
+    fn outcome(source: &str) -> String {
+        return match run(source) {
+            Ok(interpreter) => {
+                let result = global(&interpreter, "result");
+                interpreter.stringify(&result)
+            }
+            Err(error) => error.kind.to_string(),
+        };
+    }
+
+    // parse -> format -> parse must give a program that formats the same and computes the same
+    fn assert_round_trips(source: &str) {
+        let formatted = format(source);
+        let reparsed = match parse_program(&formatted) {
+            Ok(statements) => statements,
+            Err(errors) => panic!("{:?} formatted to {:?}: {:?}", source, formatted, errors),
+        };
+        assert_eq!(
+            SourceFormatter::new().format(&reparsed),
+            formatted,
+            "formatting {:?} isn't stable",
+            source
+        );
+        assert_eq!(outcome(source), outcome(&formatted), "{:?}", formatted);
+    }
+
+    // a tiny xorshift so the generated programs are the same on every run
+    struct Random(u64);
+
+    impl Random {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            return (self.0 % n as u64) as usize;
+        }
+
+        fn pick<'a>(&mut self, options: &[&'a str]) -> &'a str {
+            return options[self.below(options.len())];
+        }
+    }
+
+    fn random_expression(random: &mut Random, depth: usize) -> String {
+        if depth == 0 || random.below(4) == 0 {
+            return random
+                .pick(&[
+                    "1",
+                    "2",
+                    "7",
+                    "0.5",
+                    "2.0",
+                    "true",
+                    "false",
+                    "nil",
+                    "\"s\"",
+                    // ends in a quote, holds a triple quote, and holds escapes kept raw
+                    "\"s\\x22\"",
+                    "\"\\x22\\x22\\x22\"",
+                    "\"\"\"\\x41\\\"\"\"",
+                ])
+                .to_string();
+        }
+        let expression = match random.below(3) {
+            0 => format!(
+                // spaced, so that `- -1` isn't scanned as a decrement
+                "{} {}",
+                random.pick(&["-", "!", "~"]),
+                random_expression(random, depth - 1)
+            ),
+            _ => format!(
+                "{} {} {}",
+                random_expression(random, depth - 1),
+                random.pick(&[
+                    "+", "-", "*", "/", "<", "<=", "==", "!=", "and", "or", "??", "&", "|", "^",
+                    "<<", ">>",
+                ]),
+                random_expression(random, depth - 1)
+            ),
+        };
+        if random.below(2) == 0 {
+            return format!("({})", expression);
+        }
+        return expression;
+    }
+
+    #[test]
+    fn formatted_programs_round_trip() {
+        assert_round_trips("unless (1 > 2 or false) print 1; var result = - -1;");
+        assert_round_trips("var result = 9999999999.0 * 9999999999.0;");
+        assert_round_trips("var x = [1, 2, 3]; var result = x[1:] + [x[0]];");
+        assert_round_trips("var result = \"a\\x22\";");
+        assert_round_trips("var result = \"\"\"a\"\"\" + \"\\x22\\x22\\x22\";");
+        assert_round_trips("var result = \"\"\"\\x22 \\u{41} \\\"\"\";");
+    }
+
+    #[test]
+    fn quotes_and_backslashes_in_strings_are_escaped() {
+        assert_eq!(format("print \"a\\x22\";").trim(), "print \"a\\x22\";");
+        assert_eq!(
+            format("print \"\"\"a\\b\"\"\";").trim(),
+            "print \"a\\x5cb\";"
+        );
+    }
+
+    #[test]
+    fn random_expressions_round_trip() {
+        let mut random = Random(0x2545f4914f6cdd1d);
+        let mut checked = 0;
+        while checked < 500 {
+            let source = format!("var result = {};", random_expression(&mut random, 4));
+            // the generator doesn't know every rule, e.g. that comparisons don't chain
+            if parse_program(&source).is_err() {
+                continue;
+            }
+            assert_round_trips(&source);
+            checked += 1;
+        }
+    }
+}
@@ -1,13 +1,20 @@
-use crate::{expr::Expr, token::Token};
+use crate::{
+    expr::{Expr, NodeId, Pattern},
+    token::Token,
+};
 
 pub trait Visitor {
     type Output;
 
+    fn visit_assert(&mut self, assert: &Assert) -> Self::Output;
     fn visit_block(&mut self, block: &Block) -> Self::Output;
+    fn visit_destructure(&mut self, destructure: &Destructure) -> Self::Output;
     fn visit_expression(&mut self, stmt: &Expression) -> Self::Output;
+    fn visit_for_each(&mut self, for_each: &ForEach) -> Self::Output;
     fn visit_function(&mut self, function: &Function) -> Self::Output;
     fn visit_if(&mut self, r#if: &If) -> Self::Output;
     fn visit_print(&mut self, print: &Print) -> Self::Output;
+    fn visit_repeat(&mut self, repeat: &Repeat) -> Self::Output;
     fn visit_return(&mut self, r#return: &Return) -> Self::Output;
     fn visit_var(&mut self, var: &Var) -> Self::Output;
     fn visit_while(&mut self, r#while: &While) -> Self::Output;
@@ -15,11 +22,15 @@ pub trait Visitor {
 
 #[derive(Clone, PartialEq, Debug)]
 pub enum Stmt {
+    Assert(Assert),
     Block(Block),
+    Destructure(Destructure),
     Expression(Expression),
+    ForEach(ForEach),
     Function(Function),
     If(If),
     Print(Print),
+    Repeat(Repeat),
     Return(Return),
     Var(Var),
     While(While),
@@ -28,38 +39,170 @@ pub enum Stmt {
 impl Stmt {
     pub fn accept<T: Visitor>(&self, visitor: &mut T) -> T::Output {
         return match self {
+            Stmt::Assert(assert) => visitor.visit_assert(assert),
             Stmt::Block(block) => visitor.visit_block(block),
+            Stmt::Destructure(destructure) => visitor.visit_destructure(destructure),
             Stmt::Expression(expression) => visitor.visit_expression(expression),
+            Stmt::ForEach(for_each) => visitor.visit_for_each(for_each),
             Stmt::Function(function) => visitor.visit_function(function),
             Stmt::If(r#if) => visitor.visit_if(r#if),
             Stmt::Print(print) => visitor.visit_print(print),
+            Stmt::Repeat(repeat) => visitor.visit_repeat(repeat),
             Stmt::Return(r#return) => visitor.visit_return(r#return),
             Stmt::Var(var) => visitor.visit_var(var),
             Stmt::While(r#while) => visitor.visit_while(r#while),
         };
     }
+
+    // byte offsets (start, end) of the source this statement was parsed from
+    pub fn span(&self) -> (usize, usize) {
+        return match self {
+            Stmt::Assert(assert) => assert.span,
+            Stmt::Block(block) => block.span,
+            Stmt::Destructure(destructure) => destructure.span,
+            Stmt::Expression(expression) => expression.span,
+            Stmt::ForEach(for_each) => for_each.span,
+            Stmt::Function(function) => function.span,
+            Stmt::If(r#if) => r#if.span,
+            Stmt::Print(print) => print.span,
+            Stmt::Repeat(repeat) => repeat.span,
+            Stmt::Return(r#return) => r#return.span,
+            Stmt::Var(var) => var.span,
+            Stmt::While(r#while) => r#while.span,
+        };
+    }
+
+    #[cfg(test)]
+    pub fn id(&self) -> NodeId {
+        return match self {
+            Stmt::Assert(assert) => assert.id,
+            Stmt::Block(block) => block.id,
+            Stmt::Destructure(destructure) => destructure.id,
+            Stmt::Expression(expression) => expression.id,
+            Stmt::ForEach(for_each) => for_each.id,
+            Stmt::Function(function) => function.id,
+            Stmt::If(r#if) => r#if.id,
+            Stmt::Print(print) => print.id,
+            Stmt::Repeat(repeat) => repeat.id,
+            Stmt::Return(r#return) => r#return.id,
+            Stmt::Var(var) => var.id,
+            Stmt::While(r#while) => r#while.id,
+        };
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Assert {
+    pub keyword: Token,
+    pub condition: Box<Expr>,
+    pub message: Option<Box<Expr>>,
+    pub span: (usize, usize),
+    pub id: NodeId,
+}
+
+impl Assert {
+    pub fn new(
+        keyword: Token,
+        condition: Expr,
+        message: Option<Expr>,
+        span: (usize, usize),
+        id: NodeId,
+    ) -> Assert {
+        Assert {
+            id,
+            keyword,
+            condition: Box::new(condition),
+            message: message.map(|m| Box::new(m)),
+            span,
+        }
+    }
 }
 
 #[derive(Clone, PartialEq, Debug)]
 pub struct Block {
     pub statements: Vec<Stmt>,
+    pub span: (usize, usize),
+    pub id: NodeId,
 }
 
 impl Block {
-    pub fn new(statements: Vec<Stmt>) -> Block {
-        Block { statements }
+    pub fn new(statements: Vec<Stmt>, span: (usize, usize), id: NodeId) -> Block {
+        Block {
+            id,
+            statements,
+            span,
+        }
+    }
+}
+
+// `var [a, b] = value;`, declaring each name with an element of a list
+#[derive(Clone, PartialEq, Debug)]
+pub struct Destructure {
+    pub pattern: Pattern,
+    pub initializer: Box<Expr>,
+    pub span: (usize, usize),
+    pub id: NodeId,
+}
+
+impl Destructure {
+    pub fn new(
+        pattern: Pattern,
+        initializer: Expr,
+        span: (usize, usize),
+        id: NodeId,
+    ) -> Destructure {
+        Destructure {
+            id,
+            pattern,
+            initializer: Box::new(initializer),
+            span,
+        }
     }
 }
 
 #[derive(Clone, PartialEq, Debug)]
 pub struct Expression {
     pub expression: Box<Expr>,
+    pub span: (usize, usize),
+    pub id: NodeId,
 }
 
 impl Expression {
-    pub fn new(expression: Expr) -> Expression {
+    pub fn new(expression: Expr, span: (usize, usize), id: NodeId) -> Expression {
         Expression {
+            id,
             expression: Box::new(expression),
+            span,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct ForEach {
+    pub name: Token,
+    pub keyword: Token,
+    pub iterable: Box<Expr>,
+    pub body: Box<Stmt>,
+    pub span: (usize, usize),
+    pub id: NodeId,
+}
+
+impl ForEach {
+    pub fn new(
+        name: Token,
+        keyword: Token,
+        iterable: Expr,
+        body: Stmt,
+        span: (usize, usize),
+        id: NodeId,
+    ) -> ForEach {
+        ForEach {
+            id,
+            name,
+            keyword,
+            iterable: Box::new(iterable),
+            body: Box::new(body),
+            span,
         }
     }
 }
@@ -69,11 +212,25 @@ pub struct Function {
     pub name: Token,
     pub params: Vec<Token>,
     pub body: Vec<Stmt>,
+    pub span: (usize, usize),
+    pub id: NodeId,
 }
 
 impl Function {
-    pub fn new(name: Token, params: Vec<Token>, body: Vec<Stmt>) -> Function {
-        Function { name, params, body }
+    pub fn new(
+        name: Token,
+        params: Vec<Token>,
+        body: Vec<Stmt>,
+        span: (usize, usize),
+        id: NodeId,
+    ) -> Function {
+        Function {
+            id,
+            name,
+            params,
+            body,
+            span,
+        }
     }
 }
 
@@ -82,26 +239,68 @@ pub struct If {
     pub condition: Box<Expr>,
     pub then_branch: Box<Stmt>,
     pub else_branch: Option<Box<Stmt>>,
+    pub span: (usize, usize),
+    pub id: NodeId,
 }
 
 impl If {
-    pub fn new(condition: Expr, then_branch: Stmt, else_branch: Option<Stmt>) -> If {
+    pub fn new(
+        condition: Expr,
+        then_branch: Stmt,
+        else_branch: Option<Stmt>,
+        span: (usize, usize),
+        id: NodeId,
+    ) -> If {
         If {
+            id,
             condition: Box::new(condition),
             then_branch: Box::new(then_branch),
             else_branch: else_branch.map(|eb| Box::new(eb)),
+            span,
         }
     }
 }
+
 #[derive(Clone, PartialEq, Debug)]
 pub struct Print {
     pub expression: Box<Expr>,
+    pub span: (usize, usize),
+    pub id: NodeId,
 }
 
 impl Print {
-    pub fn new(expression: Expr) -> Print {
+    pub fn new(expression: Expr, span: (usize, usize), id: NodeId) -> Print {
         Print {
+            id,
             expression: Box::new(expression),
+            span,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Repeat {
+    pub keyword: Token,
+    pub count: Box<Expr>,
+    pub body: Box<Stmt>,
+    pub span: (usize, usize),
+    pub id: NodeId,
+}
+
+impl Repeat {
+    pub fn new(
+        keyword: Token,
+        count: Expr,
+        body: Stmt,
+        span: (usize, usize),
+        id: NodeId,
+    ) -> Repeat {
+        Repeat {
+            id,
+            keyword,
+            count: Box::new(count),
+            body: Box::new(body),
+            span,
         }
     }
 }
@@ -110,13 +309,17 @@ impl Print {
 pub struct Return {
     pub keyword: Token,
     pub value: Option<Expr>,
+    pub span: (usize, usize),
+    pub id: NodeId,
 }
 
 impl Return {
-    pub fn new(keyword: Token, value: Option<Expr>) -> Return {
+    pub fn new(keyword: Token, value: Option<Expr>, span: (usize, usize), id: NodeId) -> Return {
         Return {
+            id,
             keyword,
             value: value,
+            span,
         }
     }
 }
@@ -125,13 +328,25 @@ impl Return {
 pub struct Var {
     pub name: Token,
     pub initializer: Option<Box<Expr>>,
+    pub constant: bool,
+    pub span: (usize, usize),
+    pub id: NodeId,
 }
 
 impl Var {
-    pub fn new(name: Token, initializer: Option<Expr>) -> Var {
+    pub fn new(
+        name: Token,
+        initializer: Option<Expr>,
+        constant: bool,
+        span: (usize, usize),
+        id: NodeId,
+    ) -> Var {
         Var {
+            id,
             name,
             initializer: initializer.map(|i| Box::new(i)),
+            constant,
+            span,
         }
     }
 }
@@ -140,13 +355,76 @@ impl Var {
 pub struct While {
     pub condition: Box<Expr>,
     pub body: Box<Stmt>,
+    // only set when desugared from a for loop
+    pub increment: Option<Box<Expr>>,
+    pub loop_variable: Option<Token>,
+    pub span: (usize, usize),
+    pub id: NodeId,
 }
 
 impl While {
-    pub fn new(condition: Expr, body: Stmt) -> While {
+    pub fn new(
+        condition: Expr,
+        body: Stmt,
+        increment: Option<Expr>,
+        loop_variable: Option<Token>,
+        span: (usize, usize),
+        id: NodeId,
+    ) -> While {
         While {
+            id,
             condition: Box::new(condition),
             body: Box::new(body),
+            increment: increment.map(|i| Box::new(i)),
+            loop_variable,
+            span,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Stmt;
+    use crate::{
+        parser::{parse_program, Parser},
+        scanner::Scanner,
+    };
+
+    fn ids(statements: &[Stmt]) -> Vec<usize> {
+        return statements.iter().map(|statement| statement.id()).collect();
+    }
+
+    #[test]
+    fn parsing_the_same_source_again_gives_the_same_ids() {
+        let source = "var x = 1; print x + 2; { x = 3; }";
+        let first = ids(&parse_program(source).unwrap());
+        assert_eq!(first, ids(&parse_program(source).unwrap()));
+        // every node gets its own id, and the last one finished is the outermost
+        assert_eq!(first, [1, 5, 10]);
+    }
+
+    #[test]
+    fn a_parse_can_start_its_ids_past_another_one() {
+        let tokens = Scanner::new("print 1;".to_string())
+            .try_scan_tokens()
+            .unwrap();
+        let statements = Parser::with_first_id(tokens, 100).try_parse().unwrap();
+        assert_eq!(ids(&statements), [101]);
+    }
+
+    #[test]
+    fn statements_and_their_expressions_have_their_own_ids() {
+        let statements = parse_program("print 1 + 2;").unwrap();
+        let expression = match &statements[0] {
+            Stmt::Print(print) => print.expression.as_ref(),
+            statement => panic!("expected a print, got {:?}", statement),
+        };
+        assert_ne!(expression.id(), statements[0].id());
+    }
+
+    #[test]
+    fn node_ids_are_kept_by_clones() {
+        let statements = parse_program("fun f(a) { return a; } f(1);").unwrap();
+        assert_eq!(ids(&statements), ids(&statements.clone()));
+    }
+}
@@ -0,0 +1,41 @@
+// helpers for running lox source in tests without touching stderr or the global error flags
+
+use crate::{
+    interpreter::{Interpreter, RuntimeError},
+    natives::Capabilities,
+    parser::parse_program,
+    token::{LiteralValue, Token},
+    token_type::TokenType,
+};
+
+// runs a program that's expected to parse, returning the interpreter so globals can be read
+pub fn run(source: &str) -> Result<Interpreter, RuntimeError> {
+    let mut interpreter = Interpreter::new(Capabilities::new());
+    interpreter.try_interpret(parse_program(source).expect("source should parse"))?;
+    return Ok(interpreter);
+}
+
+// the message of the runtime error a program is expected to stop with
+pub fn run_error(source: &str) -> String {
+    return match run(source) {
+        Ok(_) => panic!("expected a runtime error from {:?}", source),
+        Err(error) => error.kind.to_string(),
+    };
+}
+
+pub fn global(interpreter: &Interpreter, name: &str) -> Option<LiteralValue> {
+    let token = Token::new(TokenType::IDENTIFIER, name.to_string(), None, 0, 0, 0);
+    return match interpreter.globals.get(&token) {
+        Ok(value) => value,
+        Err(_) => panic!("global '{}' should be defined", name),
+    };
+}
+
+// `result` as it would be printed, after running the program
+pub fn result(source: &str) -> String {
+    let interpreter = match run(source) {
+        Ok(interpreter) => interpreter,
+        Err(error) => panic!("{:?} failed: {}", source, error.kind),
+    };
+    return interpreter.stringify(&global(&interpreter, "result"));
+}
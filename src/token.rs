@@ -1,13 +1,17 @@
-use std::fmt::Display;
+use std::{cell::RefCell, fmt::Display, rc::Rc};
 
-use crate::{lox_callables::LoxCallables, token_type::TokenType};
+use crate::{interpreter::json_quote, lox_callables::LoxCallables, token_type::TokenType};
 
 #[derive(Clone, PartialEq, Debug)]
 pub enum LiteralValue {
     String(String),
     Number(f64),
+    // number literals without a '.', kept exact until they meet a float
+    Integer(i64),
     Boolean(bool),
     LoxCallable(LoxCallables),
+    // shared so every holder of the list observes mutations
+    List(Rc<RefCell<Vec<Option<LiteralValue>>>>),
 }
 
 impl Display for LiteralValue {
@@ -15,18 +19,48 @@ impl Display for LiteralValue {
         match self {
             LiteralValue::String(value) => write!(f, "{}", value),
             LiteralValue::Number(value) => write!(f, "{:?}", value),
+            // shown like a float so token and ast output don't depend on the distinction
+            LiteralValue::Integer(value) => write!(f, "{}.0", value),
             LiteralValue::Boolean(value) => write!(f, "{}", value),
             LiteralValue::LoxCallable(value) => write!(f, "{}", value),
+            LiteralValue::List(values) => write!(f, "{}", list_to_string(values, &mut Vec::new())),
         }
     }
 }
 
+// `visiting` holds the lists being shown, so one that contains itself shows as [...]
+fn list_to_string(
+    values: &Rc<RefCell<Vec<Option<LiteralValue>>>>,
+    visiting: &mut Vec<Rc<RefCell<Vec<Option<LiteralValue>>>>>,
+) -> String {
+    if visiting.iter().any(|list| Rc::ptr_eq(list, values)) {
+        return "[...]".to_string();
+    }
+
+    visiting.push(Rc::clone(values));
+    let elements: Vec<String> = values
+        .borrow()
+        .iter()
+        .map(|value| match value {
+            Some(LiteralValue::List(values)) => list_to_string(values, visiting),
+            Some(value) => value.to_string(),
+            None => "nil".to_string(),
+        })
+        .collect();
+    visiting.pop();
+    return format!("[{}]", elements.join(", "));
+}
+
 #[derive(Clone, PartialEq, Debug)]
 pub struct Token {
     pub r#type: TokenType,
     pub lexeme: String,
     pub literal: Option<LiteralValue>,
     pub line: u64,
+    // 1-based character column of the lexeme's first character, 0 for synthetic tokens
+    pub column: usize,
+    // byte offset of the lexeme's first character in the source
+    pub offset: usize,
 }
 
 impl Token {
@@ -35,15 +69,30 @@ impl Token {
         lexeme: String,
         literal: Option<LiteralValue>,
         line: u64,
+        column: usize,
+        offset: usize,
     ) -> Token {
         return Token {
             r#type,
             lexeme,
             literal,
             line,
+            column,
+            offset,
         };
     }
 
+    // a token the parser made up while desugaring, with no position in the source.
+    // errors at one are reported without a location rather than at a misleading one
+    pub fn synthetic(r#type: TokenType, lexeme: &str) -> Token {
+        return Token::new(r#type, lexeme.to_string(), None, 0, 0, 0);
+    }
+
+    // byte offset just past the lexeme's last character
+    pub fn end(&self) -> usize {
+        return self.offset + self.lexeme.len();
+    }
+
     pub fn to_string(&self) -> String {
         return format!(
             "{} {} {}",
@@ -54,4 +103,132 @@ impl Token {
                 .unwrap_or(&LiteralValue::String("null".to_string()))
         );
     }
+
+    // one json object per token, for tools that consume the scanner's output
+    pub fn to_json(&self) -> String {
+        let literal = match &self.literal {
+            None => "null".to_string(),
+            Some(LiteralValue::String(value)) => json_quote(value),
+            Some(LiteralValue::Number(value)) => value.to_string(),
+            Some(LiteralValue::Integer(value)) => value.to_string(),
+            Some(value) => json_quote(&value.to_string()),
+        };
+        return format!(
+            "{{\"type\":{},\"lexeme\":{},\"literal\":{},\"line\":{},\"col\":{}}}",
+            json_quote(&self.r#type.to_string()),
+            json_quote(&self.lexeme),
+            literal,
+            self.line,
+            self.column
+        );
+    }
+}
+
+impl From<f64> for LiteralValue {
+    fn from(value: f64) -> Self {
+        return LiteralValue::Number(value);
+    }
+}
+
+impl From<i64> for LiteralValue {
+    fn from(value: i64) -> Self {
+        return LiteralValue::Integer(value);
+    }
+}
+
+impl From<String> for LiteralValue {
+    fn from(value: String) -> Self {
+        return LiteralValue::String(value);
+    }
+}
+
+impl From<bool> for LiteralValue {
+    fn from(value: bool) -> Self {
+        return LiteralValue::Boolean(value);
+    }
+}
+
+impl TryFrom<LiteralValue> for f64 {
+    type Error = String;
+
+    fn try_from(value: LiteralValue) -> Result<Self, Self::Error> {
+        return match value {
+            LiteralValue::Number(value) => Ok(value),
+            LiteralValue::Integer(value) => Ok(value as f64),
+            _ => Err(format!("Expected a number but got '{}'.", value)),
+        };
+    }
+}
+
+impl TryFrom<LiteralValue> for String {
+    type Error = String;
+
+    fn try_from(value: LiteralValue) -> Result<Self, Self::Error> {
+        return match value {
+            LiteralValue::String(value) => Ok(value),
+            _ => Err(format!("Expected a string but got '{}'.", value)),
+        };
+    }
+}
+
+impl TryFrom<LiteralValue> for bool {
+    type Error = String;
+
+    fn try_from(value: LiteralValue) -> Result<Self, Self::Error> {
+        return match value {
+            LiteralValue::Boolean(value) => Ok(value),
+            _ => Err(format!("Expected a boolean but got '{}'.", value)),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LiteralValue, Token};
+    use crate::token_type::TokenType;
+
+    #[test]
+    fn rust_values_convert_into_literals() {
+        assert_eq!(LiteralValue::from(1.5), LiteralValue::Number(1.5));
+        assert_eq!(LiteralValue::from(2_i64), LiteralValue::Integer(2));
+        assert_eq!(
+            LiteralValue::from("a".to_string()),
+            LiteralValue::String("a".to_string())
+        );
+        assert_eq!(LiteralValue::from(true), LiteralValue::Boolean(true));
+    }
+
+    #[test]
+    fn literals_convert_back_into_rust_values() {
+        assert_eq!(f64::try_from(LiteralValue::Number(1.5)), Ok(1.5));
+        assert_eq!(f64::try_from(LiteralValue::Integer(2)), Ok(2.0));
+        assert_eq!(
+            String::try_from(LiteralValue::String("a".to_string())),
+            Ok("a".to_string())
+        );
+        assert_eq!(bool::try_from(LiteralValue::Boolean(false)), Ok(false));
+    }
+
+    #[test]
+    fn mismatched_literals_fail_to_convert() {
+        assert_eq!(
+            f64::try_from(LiteralValue::Boolean(true)),
+            Err("Expected a number but got 'true'.".to_string())
+        );
+        assert_eq!(
+            String::try_from(LiteralValue::Number(1.5)),
+            Err("Expected a string but got '1.5'.".to_string())
+        );
+        assert_eq!(
+            bool::try_from(LiteralValue::String("yes".to_string())),
+            Err("Expected a boolean but got 'yes'.".to_string())
+        );
+    }
+
+    #[test]
+    fn synthetic_tokens_have_no_position() {
+        let token = Token::synthetic(TokenType::RETURN, "return");
+        assert_eq!((token.line, token.column, token.offset), (0, 0, 0));
+        assert_eq!(token.lexeme, "return");
+    }
 }
@@ -8,6 +8,9 @@ pub enum TokenType {
     RIGHT_PAREN,
     LEFT_BRACE,
     RIGHT_BRACE,
+    LEFT_BRACKET,
+    RIGHT_BRACKET,
+    COLON,
     COMMA,
     DOT,
     MINUS,
@@ -15,6 +18,10 @@ pub enum TokenType {
     SEMICOLON,
     SLASH,
     STAR,
+    AMPERSAND,
+    PIPE,
+    CARET,
+    TILDE,
 
     // One or two character tokens.
     BANG,
@@ -25,6 +32,12 @@ pub enum TokenType {
     GREATER_EQUAL,
     LESS,
     LESS_EQUAL,
+    LESS_LESS,
+    QUESTION_QUESTION,
+    GREATER_GREATER,
+    PLUS_PLUS,
+    MINUS_MINUS,
+    DOT_DOT_DOT,
 
     // Literals.
     IDENTIFIER,
@@ -33,22 +46,31 @@ pub enum TokenType {
 
     // Keywords
     AND,
+    ASSERT,
     CLASS,
+    CONST,
     ELSE,
     FALSE,
     FUN,
     FOR,
     IF,
+    IN,
     NIL,
     OR,
     PRINT,
+    REPEAT,
     RETURN,
     SUPER,
     THIS,
     TRUE,
+    UNLESS,
     VAR,
     WHILE,
 
+    // Keyword assignments.
+    AND_EQUAL,
+    OR_EQUAL,
+
     EOF,
 }
 
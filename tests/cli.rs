@@ -0,0 +1,198 @@
+use std::{
+    env, fs,
+    process::{self, Command, Output},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+static NEXT_FILE: AtomicUsize = AtomicUsize::new(0);
+
+fn interpreter(args: &[&str]) -> Output {
+    return Command::new(env!("CARGO_BIN_EXE_interpreter-starter-rust"))
+        .args(args)
+        .output()
+        .expect("the interpreter should start");
+}
+
+// writes the source to a file of its own, so tests can run in parallel, and passes its path last
+fn interpreter_on(args: &[&str], source: &str) -> Output {
+    let id = NEXT_FILE.fetch_add(1, Ordering::SeqCst);
+    let path = env::temp_dir().join(format!("lox-cli-{}-{}.lox", process::id(), id));
+    fs::write(&path, source).expect("the source file should be writable");
+    let mut args = args.to_vec();
+    args.push(path.to_str().unwrap());
+    let output = interpreter(&args);
+    fs::remove_file(&path).unwrap();
+    return output;
+}
+
+fn stdout(output: &Output) -> String {
+    return String::from_utf8_lossy(&output.stdout).to_string();
+}
+
+fn stderr(output: &Output) -> String {
+    return String::from_utf8_lossy(&output.stderr).to_string();
+}
+
+#[test]
+fn write_prints_without_a_newline() {
+    let output = interpreter_on(&["run"], "write(\"a\"); write(1); print \"b\";");
+    assert_eq!(stdout(&output), "a1b\n");
+}
+
+// runs in the real binary, so it's also on the main thread's real stack size
+#[test]
+fn tail_recursive_countdown_of_a_million_does_not_overflow() {
+    let source = "
+        fun count(n) {
+          if (n == 0) return \"done\";
+          return count(n - 1);
+        }
+        print count(1000000);
+    ";
+    let output = interpreter_on(&["run"], source);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "done\n");
+}
+
+#[test]
+fn scanner_and_parser_errors_report_line_and_column() {
+    let output = interpreter_on(&["tokenize"], "var a = @;");
+    assert!(stderr(&output).starts_with("[line 1:9] Error: Unexpected character: @\n"));
+    let output = interpreter_on(&["run"], "print (1;");
+    assert!(stderr(&output).starts_with("[line 1:9] Error at ';': Expect ')' after expressions.\n"));
+}
+
+#[test]
+fn runtime_errors_put_a_caret_under_the_operator() {
+    let output = interpreter_on(&["run"], "var a = 1;\nprint -\"x\";");
+    assert_eq!(
+        stderr(&output),
+        "Operand must be a number.\n[line 2:7]\n    print -\"x\";\n          ^\n"
+    );
+}
+
+#[test]
+fn eprint_leaves_stdout_alone() {
+    let output = interpreter_on(&["run"], "eprint(\"oops\");");
+    assert_eq!(stdout(&output), "");
+    assert_eq!(stderr(&output), "oops\n");
+}
+
+#[test]
+fn chained_comparisons_are_parse_errors() {
+    let output = interpreter_on(&["run"], "print 1 < 2 < 3;");
+    assert_eq!(output.status.code(), Some(65));
+    assert!(stderr(&output).contains("Error at '<': Chained comparison is not allowed"));
+    // grouped, it parses and only fails at runtime on comparing a boolean
+    let output = interpreter_on(&["run"], "print (1 < 2) < 3;");
+    assert_eq!(output.status.code(), Some(70));
+}
+
+#[test]
+fn tokenize_json_lists_every_token() {
+    let output = interpreter_on(&["tokenize", "--json"], "var x = \"a\";");
+    let expected = r#"[
+  {"type":"VAR","lexeme":"var","literal":null,"line":1,"col":1},
+  {"type":"IDENTIFIER","lexeme":"x","literal":null,"line":1,"col":5},
+  {"type":"EQUAL","lexeme":"=","literal":null,"line":1,"col":7},
+  {"type":"STRING","lexeme":"\"a\"","literal":"a","line":1,"col":9},
+  {"type":"SEMICOLON","lexeme":";","literal":null,"line":1,"col":12},
+  {"type":"EOF","lexeme":"","literal":null,"line":1,"col":13}
+]
+"#;
+    assert_eq!(stdout(&output), expected);
+}
+
+#[test]
+fn infinite_loops_stop_at_the_operation_limit() {
+    let output = interpreter_on(&["run", "--max-operations=1000"], "while (true) {}");
+    assert_eq!(output.status.code(), Some(70));
+    assert!(stderr(&output).starts_with("Operation limit exceeded.\n"));
+}
+
+#[test]
+fn columns_after_tabs_follow_the_tab_width() {
+    let output = interpreter_on(&["tokenize"], "\tvar a = @;");
+    assert!(stderr(&output).starts_with("[line 1:13] Error: Unexpected character: @\n"));
+    let output = interpreter_on(&["tokenize", "--tab-width=8"], "\tvar a = @;");
+    assert!(stderr(&output).starts_with("[line 1:17] Error: Unexpected character: @\n"));
+}
+
+#[test]
+fn errors_without_a_position_are_reported_as_generated_code() {
+    let output = interpreter_on(&["run"], "var x = 1;\nmap(5, str);");
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(
+        stderr(&output),
+        "Can only iterate over lists and strings.\n[generated code]\n"
+    );
+}
+
+#[test]
+fn parse_takes_an_expression_and_parse_stmts_a_program() {
+    let output = interpreter_on(&["parse"], "1 + 2");
+    assert_eq!(stdout(&output), "(+ 1.0 2.0)\n");
+    let output = interpreter_on(&["parse-stmts"], "var a = 1;\nprint a;");
+    assert_eq!(stdout(&output), "(var a 1.0)\n(print a)\n");
+    // a program still isn't an expression
+    let output = interpreter_on(&["parse"], "var a = 1;");
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn eval_runs_the_source_given_on_the_command_line() {
+    let output = interpreter(&["run", "--eval", "print 1 + 2;"]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "3\n");
+}
+
+#[test]
+fn max_errors_stops_after_the_limit() {
+    let output = interpreter(&[
+        "run",
+        "--max-errors=2",
+        "--eval",
+        "print @; print #; print $;",
+    ]);
+    let stderr = stderr(&output);
+    assert_eq!(output.status.code(), Some(65));
+    assert!(stderr.contains("Unexpected character: @"));
+    assert!(stderr.contains("Unexpected character: #"));
+    assert!(!stderr.contains("Unexpected character: $"));
+    assert!(stderr.ends_with("too many errors; aborting.\n"));
+}
+
+#[test]
+fn max_errors_of_zero_is_rejected() {
+    let output = interpreter(&["run", "--max-errors=0", "--eval", "print @;"]);
+    assert_eq!(stderr(&output), "Invalid --max-errors value: 0\n");
+}
+
+#[test]
+fn only_loops_that_can_never_end_are_warned_about() {
+    let stuck = "
+        var done = false;
+        var n = 0;
+        while (!done) n + 1;
+    ";
+    let output = interpreter(&["run", "--max-operations=8000000", "--eval", stuck]);
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(
+        stderr(&output).matches("Warning: Loop condition").count(),
+        1
+    );
+
+    // the flag only changes at the end, but the loop does set it
+    let ends = "
+        var done = false;
+        var n = 0;
+        while (!done) {
+          n = n + 1;
+          if (n > 1000010) done = true;
+        }
+        print n;
+    ";
+    let output = interpreter(&["run", "--eval", ends]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1000011\n");
+    assert!(!stderr(&output).contains("Warning"));
+}